@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{DateTime, Local};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+pub static HOSTNAME: Lazy<String> = Lazy::new(|| {
+    std::process::Command::new("hostname")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+});
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[serde(alias = "debug")]
+    Info,
+    #[default]
+    #[serde(alias = "warn")]
+    Warning,
+    #[serde(alias = "err")]
+    Error,
+    #[serde(alias = "crit", alias = "fatal")]
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Critical => "critical",
+        }
+    }
+
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            Severity::Info => "ℹ️",
+            Severity::Warning => "⚠️",
+            Severity::Error => "❌",
+            Severity::Critical => "🚨",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An alert travelling from a source (mail, api, ...) to every sink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    /// incident id, assigned when the alert enters the broadcast channel
+    #[serde(default)]
+    pub id: u64,
+    pub title: String,
+    pub body: String,
+    pub severity: Severity,
+    pub labels: BTreeMap<String, String>,
+    pub source: String,
+    pub time: DateTime<Local>,
+    pub host: String,
+}
+
+/// Structured payload accepted by the api, e.g. `{"title": "..", "severity": "critical"}`
+#[derive(Debug, Deserialize)]
+struct AlertPayload {
+    #[serde(default)]
+    title: String,
+    #[serde(default, alias = "msg", alias = "message", alias = "text")]
+    body: String,
+    #[serde(default)]
+    severity: Option<Severity>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+impl Alert {
+    pub fn new(source: &str, body: impl Into<String>) -> Self {
+        Alert {
+            id: 0,
+            title: String::new(),
+            body: body.into(),
+            severity: Severity::default(),
+            labels: BTreeMap::new(),
+            source: source.to_string(),
+            time: Local::now(),
+            host: HOSTNAME.clone(),
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_label(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.labels.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Recovery notice of an earlier alert, it resolves the incidents with the same source and title
+    pub fn recovery(source: &str, title: impl Into<String>, body: impl Into<String>) -> Self {
+        Alert::new(source, body)
+            .with_title(title)
+            .with_severity(Severity::Info)
+            .with_label("status", "resolved")
+    }
+
+    pub fn is_recovery(&self) -> bool {
+        self.labels.get("status").is_some_and(|s| s == "resolved")
+    }
+
+    /// Parse a raw message: a json object with alert fields, otherwise plain text as body.
+    /// Returns `None` for `{"ping": ..}` keepalive messages.
+    pub fn parse(source: &str, raw: &str) -> Option<Self> {
+        let mut alert = Alert::new(source, raw);
+        if let Ok(j) = serde_json::from_str::<serde_json::Value>(raw) {
+            if j.get("ping").is_some() {
+                return None;
+            }
+            if let Ok(payload) = serde_json::from_value::<AlertPayload>(j) {
+                if !payload.title.is_empty() || !payload.body.is_empty() {
+                    alert.title = payload.title;
+                    alert.body = payload.body;
+                }
+                alert.severity = payload.severity.unwrap_or_default();
+                alert.labels = payload.labels;
+            }
+        }
+        Some(alert)
+    }
+
+    /// Sample alert used by `/preview`
+    pub fn sample(source: &str) -> Self {
+        Alert::new(source, "disk usage of /data is 91.3%, threshold 85%")
+            .with_title("Disk usage high")
+            .with_severity(Severity::Critical)
+            .with_label("mount", "/data")
+            .with_label("team", "infra")
+    }
+
+    /// Pretty printed body if it is a json document
+    pub fn payload(&self) -> Option<String> {
+        let body = self.body.trim_start();
+        if !body.starts_with('{') && !body.starts_with('[') {
+            return None;
+        }
+        serde_json::from_str::<serde_json::Value>(&self.body)
+            .ok()
+            .and_then(|j| serde_json::to_string_pretty(&j).ok())
+    }
+
+    pub fn labels_str(&self) -> String {
+        self.labels
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plain_text() {
+        let alert = Alert::parse("api", "disk full").unwrap();
+        assert_eq!((alert.title.as_str(), alert.body.as_str()), ("", "disk full"));
+        assert_eq!(alert.severity, Severity::Warning);
+    }
+
+    #[test]
+    fn parse_json_payload() {
+        let raw = r#"{"title": "Disk", "msg": "91%", "severity": "crit", "labels": {"mount": "/data"}}"#;
+        let alert = Alert::parse("api", raw).unwrap();
+        assert_eq!((alert.title.as_str(), alert.body.as_str()), ("Disk", "91%"));
+        assert_eq!(alert.severity, Severity::Critical);
+        assert_eq!(alert.labels_str(), "mount=/data");
+        assert!(alert.payload().is_none());
+    }
+
+    #[test]
+    fn parse_ping_and_unknown_json() {
+        assert!(Alert::parse("api", r#"{"ping": 1}"#).is_none());
+        let alert = Alert::parse("api", r#"{"other": 1}"#).unwrap();
+        assert_eq!(alert.body, r#"{"other": 1}"#);
+        assert!(alert.payload().is_some());
+    }
+
+    #[test]
+    fn recovery() {
+        let alert = Alert::recovery("watch", "nginx is down", "running again");
+        assert!(alert.is_recovery());
+        assert_eq!(alert.severity, Severity::Info);
+        assert!(!Alert::new("watch", "x").is_recovery());
+    }
+}
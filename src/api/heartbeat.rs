@@ -0,0 +1,47 @@
+use axum::{extract::Path, http::StatusCode};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::heartbeat as beats;
+
+pub fn heartbeat() -> OpenApiRouter {
+    OpenApiRouter::new().nest("/heartbeat", OpenApiRouter::new().routes(routes!(get_ping, post_ping)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{name}",
+    tags = ["heartbeat"],
+    params(
+        ("name" = String, Path, description = "heartbeat name from config")
+    ),
+    responses(
+        (status = 200, description = "ping recorded"),
+        (status = 404, description = "unknown heartbeat")
+    )
+)]
+async fn get_ping(Path(name): Path<String>) -> StatusCode {
+    ping(&name).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/{name}",
+    tags = ["heartbeat"],
+    params(
+        ("name" = String, Path, description = "heartbeat name from config")
+    ),
+    responses(
+        (status = 200, description = "ping recorded"),
+        (status = 404, description = "unknown heartbeat")
+    )
+)]
+async fn post_ping(Path(name): Path<String>) -> StatusCode {
+    ping(&name).await
+}
+
+async fn ping(name: &str) -> StatusCode {
+    match beats::beat(name).await {
+        true => StatusCode::OK,
+        false => StatusCode::NOT_FOUND,
+    }
+}
@@ -0,0 +1,41 @@
+use axum::{Json, extract::Path, http::StatusCode};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::incident::{self, Incident};
+
+pub fn incident() -> OpenApiRouter {
+    OpenApiRouter::new().nest(
+        "/incident",
+        OpenApiRouter::new()
+            .routes(routes!(list))
+            .routes(routes!(detail)),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/list",
+    tags = ["incident"],
+    responses(
+        (status = 200, description = "latest incidents with ack / resolve state, newest first")
+    )
+)]
+async fn list() -> Json<Vec<Incident>> {
+    Json(incident::list())
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tags = ["incident"],
+    params(
+        ("id" = u64, Path, description = "incident id")
+    ),
+    responses(
+        (status = 200, description = "incident state"),
+        (status = 404, description = "incident not found")
+    )
+)]
+async fn detail(Path(id): Path<u64>) -> Result<Json<Incident>, StatusCode> {
+    incident::get(id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
@@ -1,3 +1,5 @@
+pub mod heartbeat;
+pub mod incident;
 pub mod serve;
 pub mod webhook;
 
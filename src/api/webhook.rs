@@ -10,7 +10,7 @@ use utoipa::{
 use utoipa_axum::{router::OpenApiRouter, routes};
 use utoipa_scalar::{Scalar, Servable as ScalarServable};
 
-use crate::{boardcast::BROADCAST_SENDER};
+use crate::{alert::Alert, api::heartbeat::heartbeat, api::incident::incident, boardcast::BROADCAST_SENDER, heartbeat as beats};
 
 #[allow(dead_code)]
 static API_TOKEN: OnceCell<String> = OnceCell::new();
@@ -19,6 +19,8 @@ static API_TOKEN: OnceCell<String> = OnceCell::new();
 pub fn api() -> Router {
     let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
         .merge(alert())
+        .merge(incident())
+        .merge(heartbeat())
         .split_for_parts();
 
     if cfg!(debug_assertions) {
@@ -76,11 +78,19 @@ fn alert() -> OpenApiRouter {
 )]
 async fn webhook(body: String) -> StatusCode {
     info!("[webhook] {}", body);
-    if let Some(tx) = BROADCAST_SENDER.get() {
-        if let Err(err) = tx.send(body).await {
-            info!("Failed to send message: {}", err);
-            return StatusCode::INTERNAL_SERVER_ERROR;
-        }
+    forward("api", &body).await
+}
+
+async fn forward(source: &str, body: &str) -> StatusCode {
+    let Some(alert) = Alert::parse(source, body) else {
+        beats::beat_json(body).await;
+        return StatusCode::OK;
+    };
+    if let Some(tx) = BROADCAST_SENDER.get()
+        && let Err(err) = tx.send(alert).await
+    {
+        info!("Failed to send message: {}", err);
+        return StatusCode::INTERNAL_SERVER_ERROR;
     }
 
     StatusCode::OK
@@ -0,0 +1,65 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local};
+use log::{error, info};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+const AUDIT_LOG: &str = "logs/audit.log";
+
+static AUDIT_FILE: Lazy<Mutex<Option<File>>> = Lazy::new(|| {
+    let _ = std::fs::create_dir_all("logs");
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(AUDIT_LOG)
+        .map_err(|e| error!("[audit] failed to open {}: {}", AUDIT_LOG, e))
+        .ok();
+    Mutex::new(file)
+});
+
+/// One line of `logs/audit.log`
+#[derive(Debug, Serialize)]
+pub struct AuditEvent {
+    pub time: DateTime<Local>,
+    /// `command`, `denied_chat`, `denied_role`, `callback`, ...
+    pub kind: String,
+    pub chat_id: i64,
+    pub user_id: u64,
+    pub user: String,
+    pub action: String,
+    pub detail: String,
+}
+
+impl AuditEvent {
+    pub fn new(kind: &str, chat_id: i64, user_id: u64, user: &str, action: &str) -> Self {
+        AuditEvent {
+            time: Local::now(),
+            kind: kind.to_string(),
+            chat_id,
+            user_id,
+            user: user.to_string(),
+            action: action.to_string(),
+            detail: String::new(),
+        }
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = detail.into();
+        self
+    }
+}
+
+pub fn record(event: AuditEvent) {
+    let Ok(line) = serde_json::to_string(&event) else {
+        return;
+    };
+    info!("[audit] {}", line);
+    if let Some(file) = AUDIT_FILE.lock().unwrap().as_mut()
+        && let Err(e) = writeln!(file, "{}", line)
+    {
+        error!("[audit] failed to write: {}", e);
+    }
+}
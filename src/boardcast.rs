@@ -3,21 +3,36 @@ use once_cell::sync::OnceCell;
 // use tokio::sync::broadcast::{self, Receiver as BroadcastReceiver, Sender as BroadcastSender};
 use tokio::sync::mpsc::{self, Sender, Receiver};
 
-use crate::{bot::BOTS_TX, webhook::HOOK_TX, G_TOKIO_RUNTIME};
+use log::info;
 
-pub static BROADCAST_SENDER: OnceCell<Sender<String>> = OnceCell::new();
+use crate::{alert::Alert, bot::{self, BOTS_TX}, incident, webhook::HOOK_TX, G_TOKIO_RUNTIME};
+
+pub static BROADCAST_SENDER: OnceCell<Sender<Alert>> = OnceCell::new();
 // pub static BROADCAST_RECEIVER: OnceCell<Sender<String>> = OnceCell::new();
 
 pub fn init_channel() -> anyhow::Result<()> {
     // let (sender, mut receiver) = broadcast::channel(32);
-    let (sender, mut receiver): (Sender<String>, Receiver<String>) = mpsc::channel(32);
+    let (sender, mut receiver): (Sender<Alert>, Receiver<Alert>) = mpsc::channel(32);
     BROADCAST_SENDER
         .set(sender)
         .map_err(|_| anyhow::anyhow!("Failed to set broadcast sender"))?;
     // BROADCAST_RECEIVER.set(receiver).map_err(|_| anyhow::anyhow!("Failed to set broadcast receiver"))?;
 
     G_TOKIO_RUNTIME.spawn(async move {
-        while let Some(msg) = receiver.recv().await {
+        while let Some(mut msg) = receiver.recv().await {
+            // a silence hides the recovery notice, not the recovery itself
+            if msg.is_recovery() {
+                for resolved in incident::resolve_recovered(&msg) {
+                    info!("[boardcast] incident {} recovered", resolved.id);
+                    bot::refresh(&resolved).await;
+                }
+            }
+            if incident::is_silenced(&msg) {
+                info!("[boardcast] silenced: {}", incident::fingerprint(&msg));
+                continue;
+            }
+            incident::open(&mut msg);
+
             // to bots
             if let Some(tx) = BOTS_TX.get() {
                 tx.send(msg.clone()).unwrap();
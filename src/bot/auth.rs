@@ -0,0 +1,123 @@
+use log::{info, warn};
+use teloxide::prelude::*;
+
+use crate::audit::{self, AuditEvent};
+use crate::bot::callback::user_name;
+use crate::bot::command::Command;
+use crate::config::{Role, UserRef, CONFIG};
+use crate::escalation;
+
+/// Minimum role of each command unless overridden by `[telegram.permissions]`
+fn default_role(command: &str) -> Role {
+    match command {
+        "help" | "chatid" | "start" => Role::Anyone,
+        "exit" | "shell" => Role::Admin,
+        "mock" | "override" | "svc restart" | "docker restart" => Role::Operator,
+        _ => Role::Viewer,
+    }
+}
+
+pub fn command_name(cmd: &Command) -> String {
+    format!("{:?}", cmd).to_lowercase()
+}
+
+pub fn required_role(command: &str) -> Role {
+    CONFIG
+        .telegram
+        .permissions
+        .get(command)
+        .copied()
+        .unwrap_or_else(|| default_role(command))
+}
+
+fn is(user_id: u64, users: &[UserRef]) -> bool {
+    users.iter().any(|u| match u {
+        UserRef::Id(id) => *id == user_id,
+        UserRef::Name(name) => CONFIG
+            .telegram
+            .users
+            .get(name)
+            .is_some_and(|user| user.id == user_id as i64),
+    })
+}
+
+pub fn role_of(user_id: u64) -> Role {
+    let Some(roles) = &CONFIG.telegram.roles else {
+        return Role::Viewer;
+    };
+    if is(user_id, &roles.admins) {
+        Role::Admin
+    } else if is(user_id, &roles.operators) {
+        Role::Operator
+    } else if is(user_id, &roles.viewers) {
+        Role::Viewer
+    } else {
+        Role::Anyone
+    }
+}
+
+pub fn allowed(user_id: u64, command: &str) -> bool {
+    role_of(user_id) >= required_role(command)
+}
+
+pub fn chat_allowed(chat_id: ChatId) -> bool {
+    let chat_id = chat_id.to_string();
+    match &CONFIG.telegram.command_chat_id {
+        Some(chats) => chats.contains(&chat_id),
+        None => CONFIG.telegram.allow_chat_id.iter().any(|c| c.chat_id() == chat_id),
+    }
+}
+
+/// Chats the bot posts buttons to: alert and command chats, escalation chats and
+/// direct messages to known users
+pub fn callback_chat_allowed(chat_id: ChatId) -> bool {
+    let id = chat_id.to_string();
+    chat_allowed(chat_id)
+        || CONFIG.telegram.allow_chat_id.iter().any(|c| c.chat_id() == id)
+        || CONFIG.telegram.users.values().any(|u| u.id == chat_id.0)
+        || escalation::chat_targets().any(|c| c == id)
+}
+
+fn audit_event(kind: &str, msg: &Message, command: &str) -> AuditEvent {
+    let (user_id, user) = msg
+        .from
+        .as_ref()
+        .map(|u| (u.id.0, user_name(u)))
+        .unwrap_or_default();
+    AuditEvent::new(kind, msg.chat.id.0, user_id, &user, command)
+        .detail(msg.text().unwrap_or_default())
+}
+
+/// Dispatcher filter: commands are only handled in allowed chats
+pub async fn allowed_chat(bot: Bot, msg: Message, cmd: Command) -> bool {
+    if chat_allowed(msg.chat.id) {
+        return true;
+    }
+    let command = command_name(&cmd);
+    warn!("[auth] /{} from unknown chat {}", command, msg.chat.id);
+    audit::record(audit_event("denied_chat", &msg, &command));
+    if let Some(reply) = &CONFIG.telegram.unknown_chat_reply {
+        let _ = bot.send_message(msg.chat.id, reply).await;
+    }
+    false
+}
+
+/// Dispatcher filter run before `answer`, replies to users lacking the role
+pub async fn authorized(bot: Bot, msg: Message, cmd: Command) -> bool {
+    let command = command_name(&cmd);
+    let user_id = msg.from.as_ref().map(|u| u.id.0).unwrap_or_default();
+    if allowed(user_id, &command) {
+        info!("[auth] user {} runs /{} in chat {}", user_id, command, msg.chat.id);
+        audit::record(audit_event("command", &msg, &command));
+        return true;
+    }
+    warn!(
+        "[auth] unauthorized /{} attempt from user {} in chat {}",
+        command, user_id, msg.chat.id
+    );
+    audit::record(audit_event("denied_role", &msg, &command));
+    let _ = bot
+        .send_message(msg.chat.id, "You are not authorized to use this command.")
+        .await;
+    false
+}
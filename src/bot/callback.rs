@@ -0,0 +1,217 @@
+use chrono::Duration;
+use log::{info, warn};
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, User};
+
+use crate::audit::{self, AuditEvent};
+use crate::bot::auth::{allowed, callback_chat_allowed, role_of};
+use crate::bot::{confirm, shell};
+use crate::bot::format::{split_message, tg_len};
+use crate::bot::telegram::parse_mode;
+use crate::config::{TemplateFormat, CONFIG};
+use crate::incident::{self, Incident, IncidentStatus};
+use crate::runbook;
+use crate::template;
+
+/// Ack / Resolve / Silence buttons for an incident and the runbook actions of its route,
+/// only the actions are kept once resolved
+pub fn keyboard(incident: &Incident, route: Option<usize>) -> Option<InlineKeyboardMarkup> {
+    let id = incident.id;
+    let button = |text: &str, data: String| InlineKeyboardButton::callback(text, data);
+    let mut rows = vec![];
+    match incident.status {
+        IncidentStatus::Firing => rows.push(vec![
+            button("✅ Ack", format!("ack:{}", id)),
+            button("☑️ Resolve", format!("resolve:{}", id)),
+        ]),
+        IncidentStatus::Acked => rows.push(vec![button("☑️ Resolve", format!("resolve:{}", id))]),
+        IncidentStatus::Resolved => {}
+    }
+    if incident.status != IncidentStatus::Resolved {
+        rows.push(vec![
+            button("🔕 Silence 1h", format!("silence:{}:1", id)),
+            button("🔕 Silence 24h", format!("silence:{}:24", id)),
+        ]);
+    }
+    if let Some((index, route)) = route.and_then(|i| CONFIG.telegram.route.get(i).map(|r| (i, r))) {
+        let actions: Vec<_> = route
+            .action
+            .iter()
+            .enumerate()
+            .map(|(j, action)| button(&format!("▶️ {}", action.label), format!("run:{}:{}:{}", id, index, j)))
+            .collect();
+        rows.extend(actions.chunks(2).map(|row| row.to_vec()));
+    }
+    if rows.is_empty() {
+        return None;
+    }
+    Some(InlineKeyboardMarkup::new(rows))
+}
+
+pub fn user_name(user: &User) -> String {
+    match &user.username {
+        Some(username) => format!("@{}", username),
+        None => user.full_name(),
+    }
+}
+
+/// Lines appended to the alert message describing who handled it
+pub fn status_lines(incident: &Incident, format: TemplateFormat) -> String {
+    let escape = |s: &str| template::escape(s, format);
+    let time_fmt = "%Y-%m-%d %H:%M:%S";
+    let mut lines = vec![];
+    if let (Some(by), Some(at)) = (&incident.acked_by, incident.acked_at) {
+        lines.push(escape(&format!("✅ Acked by {} at {}", by, at.format(time_fmt))));
+    }
+    if let (Some(by), Some(at)) = (&incident.resolved_by, incident.resolved_at) {
+        lines.push(escape(&format!("☑️ Resolved by {} at {}", by, at.format(time_fmt))));
+    }
+    if let (Some(by), Some(until)) = (&incident.silenced_by, incident.silenced_until) {
+        lines.push(escape(&format!("🔕 Silenced by {} until {}", by, until.format(time_fmt))));
+    }
+    lines.join("\n")
+}
+
+/// Re-render every telegram message of the incident with its current state
+pub async fn refresh(bot: &Bot, incident: &Incident) {
+    for sent in &incident.messages {
+        let status = status_lines(incident, sent.format);
+        let text = if status.is_empty() {
+            sent.text.clone()
+        } else {
+            // the status lines must not push the message over the limit
+            let limit = CONFIG.telegram.max_message_len.saturating_sub(tg_len(&status) + 4);
+            let chunks = split_message(&sent.text, sent.format, limit);
+            let body = match chunks.len() {
+                0 | 1 => sent.text.trim_end().to_string(),
+                _ => format!("{}\n…", chunks[0].trim_end()),
+            };
+            format!("{}\n\n{}", body, status)
+        };
+        let mut req = bot.edit_message_text(sent.chat_id, sent.message_id, text);
+        req.parse_mode = parse_mode(sent.format);
+        req.reply_markup = keyboard(incident, sent.route);
+        if let Err(e) = req.await {
+            warn!("[bot] failed to edit message of incident {}: {}", incident.id, e);
+        }
+    }
+}
+
+pub async fn callback(bot: Bot, q: CallbackQuery) -> ResponseResult<()> {
+    let data = q.data.clone().unwrap_or_default();
+    let user = user_name(&q.from);
+    info!("[bot] callback {} from {}", data, user);
+
+    let mut parts = data.split(':');
+    let action = parts.next().unwrap_or_default();
+    let id = parts.next().and_then(|id| id.parse::<u64>().ok());
+    let Some(id) = id else {
+        bot.answer_callback_query(q.id).text("Unknown action").await?;
+        return Ok(());
+    };
+    let chat_id = q.message.as_ref().map(|m| m.chat().id.0).unwrap_or_default();
+    if !callback_chat_allowed(ChatId(chat_id)) {
+        warn!("[auth] {} from unknown chat {} by user {}", action, chat_id, q.from.id);
+        audit::record(AuditEvent::new("denied_chat", chat_id, q.from.id.0, &user, action).detail(data));
+        bot.answer_callback_query(q.id)
+            .text("This chat is not allowed.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+    if action == "confirm" || action == "cancel" {
+        return confirm::handle(bot, q, action, id).await;
+    }
+    if !allowed(q.from.id.0, action) {
+        warn!("[auth] unauthorized {} attempt from user {}", action, q.from.id);
+        audit::record(AuditEvent::new("denied_role", chat_id, q.from.id.0, &user, action).detail(data));
+        bot.answer_callback_query(q.id)
+            .text("You are not authorized to do this.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+    if action == "run" {
+        let route = parts.next().and_then(|i| i.parse::<usize>().ok());
+        let index = parts.next().and_then(|i| i.parse::<usize>().ok());
+        return run_action(bot, q, id, route, index).await;
+    }
+    let (incident, reply) = match action {
+        "ack" => (incident::ack(id, &user), "Acknowledged".to_string()),
+        "resolve" => (incident::resolve(id, &user), "Resolved".to_string()),
+        "silence" => {
+            let hours = parts.next().and_then(|h| h.parse::<i64>().ok()).unwrap_or(1);
+            (
+                incident::silence(id, &user, Duration::hours(hours)),
+                format!("Silenced for {}h", hours),
+            )
+        }
+        _ => (None, "Unknown action".to_string()),
+    };
+
+    audit::record(AuditEvent::new("callback", chat_id, q.from.id.0, &user, action).detail(data));
+    match incident {
+        Some(incident) => {
+            bot.answer_callback_query(q.id).text(reply).await?;
+            refresh(&bot, &incident).await;
+        }
+        None => {
+            bot.answer_callback_query(q.id)
+                .text("Alert expired or unknown")
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Run the runbook behind an alert button, replying in the thread of the alert
+async fn run_action(
+    bot: Bot,
+    q: CallbackQuery,
+    id: u64,
+    route: Option<usize>,
+    index: Option<usize>,
+) -> ResponseResult<()> {
+    let user = user_name(&q.from);
+    let action = route
+        .zip(index)
+        .and_then(|(route, index)| CONFIG.telegram.route.get(route)?.action.get(index));
+    let (Some(incident), Some(action), Some(msg)) = (incident::get(id), action, q.regular_message()) else {
+        bot.answer_callback_query(q.id).text("Alert expired or unknown").await?;
+        return Ok(());
+    };
+    let Some(rb) = runbook::find(&action.runbook) else {
+        bot.answer_callback_query(q.id).text("Unknown runbook").await?;
+        return Ok(());
+    };
+    let (chat_id, thread_id) = (msg.chat.id, msg.thread_id);
+    let data = q.data.clone().unwrap_or_default();
+    if role_of(q.from.id.0) < rb.role {
+        warn!("[auth] unauthorized runbook {} attempt from user {}", rb.name, q.from.id);
+        audit::record(AuditEvent::new("denied_role", chat_id.0, q.from.id.0, &user, "run").detail(data));
+        bot.answer_callback_query(q.id)
+            .text("You are not authorized to run this runbook.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+    let params = match runbook::bind_labels(rb, &incident.alert.labels) {
+        Ok(params) => params,
+        Err(reason) => {
+            bot.answer_callback_query(q.id).text(reason).show_alert(true).await?;
+            return Ok(());
+        }
+    };
+    audit::record(
+        AuditEvent::new("callback", chat_id.0, q.from.id.0, &user, "run")
+            .detail(runbook::argv(rb, &params).join(" ")),
+    );
+    let author = q.from.username.clone().unwrap_or("unknown".to_string());
+    if rb.confirm {
+        bot.answer_callback_query(q.id).await?;
+        return confirm::ask_runbook(bot, chat_id, thread_id, q.from.id.0, author, rb, params).await;
+    }
+    bot.answer_callback_query(q.id).text(format!("Running {}", rb.name)).await?;
+    shell::spawn_runbook(bot, chat_id, thread_id, author, rb, params);
+    Ok(())
+}
@@ -1,13 +1,36 @@
-use chrono::Local;
+use chrono::{Local, Utc};
+use std::path::PathBuf;
+use std::time::Duration;
+
 use log::{error, info};
-use sysinfo::{Disks, Networks, Pid, System};
-use teloxide::utils::markdown::escape;
+use sysinfo::{Networks, Pid, System};
+use teloxide::types::InputFile;
+use teloxide::utils::html;
 use teloxide::{prelude::*, utils::command::BotCommands};
 
 // use crate::mail::EMAIL_HISTORY;
 use crate::G_TOKIO_RUNTIME;
+use crate::alert::{Alert, HOSTNAME};
 use crate::boardcast::BROADCAST_SENDER;
 use crate::bot::STATUS;
+use crate::bot::auth::{allowed, role_of};
+use crate::bot::{confirm, shell};
+use crate::bot::format::tg_len;
+use crate::bot::telegram::{send_document, send_rendered};
+use crate::exec;
+use crate::config::{TemplateFormat, CONFIG};
+use crate::certs;
+use crate::checks;
+use crate::disk;
+use crate::docker;
+use crate::logs;
+use crate::history;
+use crate::monitor;
+use crate::oncall;
+use crate::runbook;
+use crate::systemd;
+use crate::util::{format_duration, parse_duration};
+use crate::template::{self, Rendered, SINKS};
 
 #[derive(BotCommands, Clone)]
 #[command(
@@ -36,16 +59,54 @@ pub enum Command {
     Top,
     #[command(description = "查看进程信息")]
     Peek,
+    #[command(description = "preview alert template: /preview [sink] [source]")]
+    Preview,
+    #[command(description = "who is on call: /oncall [next] [schedule]")]
+    Oncall,
+    #[command(description = "take over on-call: /override <user> <duration> [schedule]")]
+    Override,
+    #[command(description = "run a runbook: /run <name> [params..]")]
+    Run,
+    #[command(description = "last lines of a log: /tail <log> [lines]")]
+    Tail,
+    #[command(description = "search a log: /grep [-C n] <log> <regex>")]
+    Grep,
+    #[command(description = "systemd units: /svc list | status <unit> | restart <unit>")]
+    Svc,
+    #[command(description = "containers: /docker ps | stats | logs <name> [lines] | restart <name>")]
+    Docker,
+    #[command(description = "status of the uptime checks")]
+    Checks,
+    #[command(description = "expiry of the monitored tls certificates")]
+    Certs,
+    #[command(description = "chart of a metric: /graph <cpu|mem|swap|disk|inode|load|net_rx|net_tx> [6h]")]
+    Graph,
+    #[command(description = "disk usage of every filesystem: /df [path]")]
+    Df,
     // #[command(description = "查看邮件")]
     // Mails,
 }
 
 pub async fn answer(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()> {
     info!("{:?}", msg);
+    if confirm::required(&msg) {
+        return confirm::ask(bot, msg, cmd).await;
+    }
+    run(bot, msg, cmd).await
+}
+
+/// Execute a command, destructive ones only after confirmation
+pub async fn run(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()> {
     match cmd {
         Command::Help => {
-            bot.send_message(msg.chat.id, Command::descriptions().to_string())
-                .await?;
+            let mut text = Command::descriptions().to_string();
+            if !CONFIG.runbook.is_empty() {
+                text.push_str("\n\nRunbooks:");
+                for rb in &CONFIG.runbook {
+                    text.push_str(&format!("\n/run {} - {}", runbook::usage(rb), rb.description));
+                }
+            }
+            bot.send_message(msg.chat.id, text).await?;
         }
         Command::ChatId => {
             bot.send_message(msg.chat.id, format!("Your chat id is: {}", msg.chat.id))
@@ -66,7 +127,7 @@ pub async fn answer(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()>
         Command::Mock => {
             // get args
             let id = msg.chat.id;
-            let msg = msg.text().unwrap_or_else(|| "").to_string();
+            let msg = msg.text().unwrap_or_default().to_string();
             // rm /mock prefix
             let msg = msg.trim_start_matches("/mock ").trim().to_string();
             if msg.is_empty() || msg == "/mock" {
@@ -75,66 +136,52 @@ pub async fn answer(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()>
                 return Ok(());
             }
             info!("[bot] mock recv alert msg: {:?}", msg);
-            BROADCAST_SENDER
-                .get()
-                .unwrap()
-                .send(msg.to_string())
-                .await
-                .unwrap();
+            if let Some(alert) = Alert::parse("mock", &msg) {
+                BROADCAST_SENDER
+                    .get()
+                    .unwrap()
+                    .send(alert)
+                    .await
+                    .unwrap();
+            }
         }
         Command::Exit => {
-            if msg.chat.id.to_string() == STATUS.get().unwrap().admin_chat_id[0] {
-                info!("[bot] exit command received, shutting down...");
-                bot.send_message(msg.chat.id, "Shutting down...").await?;
-                std::process::exit(0);
-            } else {
-                bot.send_message(msg.chat.id, "You are not authorized to use this command.")
-                    .await?;
-                error!(
-                    "Unauthorized exit command attempt from chat id: {}",
-                    msg.chat.id
-                );
-            }
+            info!("[bot] exit command received, shutting down...");
+            bot.send_message(msg.chat.id, "Shutting down...").await?;
+            std::process::exit(0);
         }
         Command::Shell => {
-            let user = msg.chat.id.to_string();
-            if user != STATUS.get().unwrap().admin_chat_id[0] {
-                bot.send_message(msg.chat.id, "You are not authorized to use this command.")
+            let user = msg
+                .from
+                .as_ref()
+                .and_then(|u| u.username.clone())
+                .unwrap_or("unknown".to_string());
+            let hostname = HOSTNAME.as_str();
+            let cmd = msg.text().unwrap_or_default().to_string();
+            // rm `/shell` or `/shell@botname`
+            let cmd = cmd.split_once(char::is_whitespace).map_or("", |(_, args)| args).trim().to_string();
+            if cmd.is_empty() {
+                bot.send_message(msg.chat.id, "Please provide a shell command.")
                     .await?;
-                error!(
-                    "Unauthorized shell command attempt from chat id: {}",
-                    msg.chat.id
-                );
                 return Ok(());
             }
-
-            let user = msg.chat.username().unwrap_or("unknown");
-            let hostname = get_hostname();
-            let cmd = msg.text().unwrap_or_else(|| "").to_string();
-            // rm /shell prefix
-            let cmd = cmd.trim_start_matches("/shell ").trim().to_string();
-            if cmd.is_empty() || cmd == "/shell" {
-                bot.send_message(msg.chat.id, "Please provide a shell command.")
-                    .await?;
+            if let Err(reason) = exec::check_policy(&cmd) {
+                info!("[bot] shell command refused: {}, {}", cmd, reason);
+                bot.send_message(msg.chat.id, format!("Refused: {}", reason)).await?;
                 return Ok(());
             }
             info!("[bot] shell command: {}", cmd);
-            let output = run_shell(cmd.clone());
-            let cmd = escape(&cmd);
-            let output = output;
-            let fmt = format!(
-                "<b>{}@{}</b> &gt; <code>{}</code>\n<pre>{}</pre>",
-                user, hostname, cmd, output
-            );
-            bot.send_message(msg.chat.id, fmt)
-                .parse_mode(teloxide::types::ParseMode::Html)
-                .await?;
+            let header = shell::prompt(&user, hostname, &cmd);
+            let timeout = Duration::from_secs(CONFIG.shell.timeout_secs);
+            // run in background so the chat keeps answering commands
+            tokio::spawn(async move {
+                if let Err(e) = shell::stream(bot, msg.chat.id, msg.thread_id, header, exec::sh(&cmd), timeout).await {
+                    error!("[bot] shell command failed: {}", e);
+                }
+            });
         }
         Command::Metrics => {
-            let metric = metric();
-            bot.send_message(msg.chat.id, metric)
-                .parse_mode(teloxide::types::ParseMode::Html)
-                .await?;
+            send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(metric())).await?;
         }
         Command::Top => {
             let top = top();
@@ -146,7 +193,7 @@ pub async fn answer(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()>
         Command::Peek => {
             let arg1 = msg
                 .text()
-                .unwrap_or_else(|| "")
+                .unwrap_or_default()
                 .trim_start_matches("/peek ")
                 .trim()
                 .to_string();
@@ -159,10 +206,383 @@ pub async fn answer(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()>
             }
             let peek = peek(arg1);
             info!("[bot] peek command: {}", peek);
-            bot.send_message(msg.chat.id, peek)
-                .parse_mode(teloxide::types::ParseMode::Html)
-                .await?;
+            send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(peek)).await?;
         },
+        Command::Preview => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let mut args = args.split_whitespace().skip(1);
+            let sink = args.next().unwrap_or("telegram");
+            let source = args.next().unwrap_or("mock");
+            if !SINKS.contains(&sink) {
+                bot.send_message(msg.chat.id, format!("Unknown sink {}, expect one of {:?}", sink, SINKS))
+                    .await?;
+                return Ok(());
+            }
+            let alert = Alert::sample(source);
+            let template = template::select(sink, source);
+            let rendered = template.render(&alert, "keyword");
+            info!("[bot] preview sink: {}, source: {}", sink, source);
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "template sink: {}, source: {}, format: {:?}",
+                    template.sink, template.source, template.format
+                ),
+            )
+            .await?;
+            send_rendered(&bot, msg.chat.id, msg.thread_id, None, &rendered).await?;
+        }
+        Command::Oncall => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let mut args = args.split_whitespace().skip(1).peekable();
+            let next = args.next_if_eq(&"next").is_some();
+            let name = args.next();
+            let schedules: Vec<_> = CONFIG
+                .oncall
+                .iter()
+                .filter(|s| name.is_none_or(|name| s.name == name))
+                .collect();
+            if schedules.is_empty() {
+                bot.send_message(msg.chat.id, "No on-call schedule found.").await?;
+                return Ok(());
+            }
+            let now = Utc::now();
+            let mut text = String::new();
+            for schedule in schedules {
+                let shift = if next {
+                    oncall::next_shift(schedule, now)
+                } else {
+                    oncall::on_call(schedule, now)
+                };
+                if let Some(shift) = shift {
+                    text.push_str(&format!(
+                        "<b>{}</b>: {}\n{}\n\n",
+                        html::escape(&schedule.name),
+                        oncall::mention(&shift.user, TemplateFormat::Html),
+                        oncall::format_shift(schedule, &shift)
+                    ));
+                }
+            }
+            send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(text)).await?;
+        }
+        Command::Override => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let args: Vec<&str> = args.split_whitespace().skip(1).collect();
+            let (Some(user), Some(duration)) = (args.first(), args.get(1).and_then(|d| parse_duration(d))) else {
+                bot.send_message(msg.chat.id, "Usage: /override <user> <duration> [schedule], e.g. /override @kylin 12h")
+                    .await?;
+                return Ok(());
+            };
+            let schedule = match args.get(2) {
+                Some(name) => oncall::schedule(name),
+                None => CONFIG.oncall.first(),
+            };
+            let Some(schedule) = schedule else {
+                bot.send_message(msg.chat.id, "No on-call schedule found.").await?;
+                return Ok(());
+            };
+            let Some((name, _)) = oncall::find_user(user) else {
+                bot.send_message(msg.chat.id, format!("Unknown user {}, add it to [telegram.users]", user))
+                    .await?;
+                return Ok(());
+            };
+            let Some(shift) = oncall::add_override(schedule, name, duration) else {
+                bot.send_message(msg.chat.id, format!("Override of {} is out of range.", format_duration(duration)))
+                    .await?;
+                return Ok(());
+            };
+            info!("[bot] oncall override {} on {} for {}", name, schedule.name, format_duration(duration));
+            let text = format!(
+                "<b>{}</b>: {} is now on call\n{}",
+                html::escape(&schedule.name),
+                oncall::mention(name, TemplateFormat::Html),
+                oncall::format_shift(schedule, &shift)
+            );
+            send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(text)).await?;
+        }
+        Command::Run => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let args: Vec<&str> = args.split_whitespace().skip(1).collect();
+            let Some(name) = args.first() else {
+                let list: Vec<String> = CONFIG.runbook.iter().map(|rb| format!("/run {}", runbook::usage(rb))).collect();
+                let text = if list.is_empty() { "No runbook configured.".to_string() } else { list.join("\n") };
+                bot.send_message(msg.chat.id, text).await?;
+                return Ok(());
+            };
+            let Some(rb) = runbook::find(name) else {
+                bot.send_message(msg.chat.id, format!("Unknown runbook {}", name)).await?;
+                return Ok(());
+            };
+            let user_id = msg.from.as_ref().map(|u| u.id.0).unwrap_or_default();
+            if role_of(user_id) < rb.role {
+                info!("[bot] runbook {} refused for user {}", rb.name, user_id);
+                bot.send_message(msg.chat.id, "You are not authorized to run this runbook.").await?;
+                return Ok(());
+            }
+            let params = match runbook::bind(rb, &args[1..]) {
+                Ok(params) => params,
+                Err(reason) => {
+                    bot.send_message(msg.chat.id, reason).await?;
+                    return Ok(());
+                }
+            };
+            let user = msg
+                .from
+                .as_ref()
+                .and_then(|u| u.username.clone())
+                .unwrap_or("unknown".to_string());
+            shell::spawn_runbook(bot, msg.chat.id, msg.thread_id, user, rb, params);
+        }
+        Command::Tail => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let args: Vec<&str> = args.split_whitespace().skip(1).collect();
+            let Some(path) = log_file(&bot, &msg, args.first().copied()).await? else {
+                return Ok(());
+            };
+            let lines = args.get(1).and_then(|n| n.parse::<usize>().ok()).unwrap_or(50).min(logs::MAX_TAIL);
+            let output = tokio::task::spawn_blocking(move || logs::tail(&path, lines).map(|text| (path, text)))
+                .await
+                .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            match output {
+                Ok((path, text)) => {
+                    let title = format!("{} (last {} lines)", path.display(), lines);
+                    send_output(&bot, &msg, &title, text).await?;
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("Failed to read log: {}", e)).await?;
+                }
+            }
+        }
+        Command::Grep => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let mut args: Vec<&str> = args.split_whitespace().skip(1).collect();
+            let mut context = 0;
+            if args.first() == Some(&"-C") && args.len() > 1 {
+                context = args[1].parse::<usize>().unwrap_or(0).min(20);
+                args.drain(..2);
+            }
+            let pattern = args.get(1..).unwrap_or_default().join(" ");
+            if pattern.is_empty() {
+                bot.send_message(msg.chat.id, "Usage: /grep [-C n] <log> <regex>").await?;
+                return Ok(());
+            }
+            let re = match regex::Regex::new(&pattern) {
+                Ok(re) => re,
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("Invalid regex: {}", e)).await?;
+                    return Ok(());
+                }
+            };
+            let Some(path) = log_file(&bot, &msg, args.first().copied()).await? else {
+                return Ok(());
+            };
+            let output = tokio::task::spawn_blocking(move || logs::grep(&path, &re, context).map(|out| (path, out)))
+                .await
+                .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            match output {
+                Ok((path, (_, 0))) => {
+                    bot.send_message(msg.chat.id, format!("No match in {}", path.display())).await?;
+                }
+                Ok((path, (text, matches))) => {
+                    let limit = if matches == logs::MAX_MATCHES { " (limit reached)" } else { "" };
+                    let title = format!("{}: {} matches{}", path.display(), matches, limit);
+                    send_output(&bot, &msg, &title, text).await?;
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("Failed to read log: {}", e)).await?;
+                }
+            }
+        }
+        Command::Svc => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let args: Vec<&str> = args.split_whitespace().skip(1).collect();
+            match (args.first().copied(), args.get(1).copied()) {
+                (Some("list") | None, _) => {
+                    if CONFIG.systemd.units.is_empty() {
+                        bot.send_message(msg.chat.id, "No unit configured in [systemd].").await?;
+                        return Ok(());
+                    }
+                    let mut text = String::new();
+                    for unit in &CONFIG.systemd.units {
+                        let line = match systemd::status(unit).await {
+                            Ok(status) => format!("{} <b>{}</b>: {}", status.emoji(), html::escape(unit), status.summary()),
+                            Err(e) => format!("⚪ <b>{}</b>: {}", html::escape(unit), html::escape(&e)),
+                        };
+                        text.push_str(&line);
+                        text.push('\n');
+                    }
+                    send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(text)).await?;
+                }
+                (Some(action @ ("status" | "restart")), Some(name)) => {
+                    let Some(unit) = systemd::find(name) else {
+                        bot.send_message(msg.chat.id, format!("Unit {} is not in the allowlist.", name)).await?;
+                        return Ok(());
+                    };
+                    let user_id = msg.from.as_ref().map(|u| u.id.0).unwrap_or_default();
+                    if action == "restart" {
+                        if !allowed(user_id, "svc restart") {
+                            bot.send_message(msg.chat.id, "You are not authorized to restart services.").await?;
+                            return Ok(());
+                        }
+                        info!("[bot] restarting unit {}", unit);
+                        match systemd::restart(unit).await {
+                            Ok(result) if result.success() => {}
+                            Ok(result) => {
+                                let text = format!(
+                                    "Failed to restart <b>{}</b>: {}\n<pre>{}</pre>",
+                                    html::escape(unit),
+                                    html::escape(&result.status()),
+                                    html::escape(result.output.trim())
+                                );
+                                send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(text)).await?;
+                                return Ok(());
+                            }
+                            Err(e) => {
+                                bot.send_message(msg.chat.id, e).await?;
+                                return Ok(());
+                            }
+                        }
+                    }
+                    let status = match systemd::status(unit).await {
+                        Ok(status) => status,
+                        Err(e) => {
+                            bot.send_message(msg.chat.id, e).await?;
+                            return Ok(());
+                        }
+                    };
+                    let journal = systemd::journal(unit, CONFIG.systemd.journal_lines).await.unwrap_or_else(|e| e);
+                    let restarted = if action == "restart" { "restarted, " } else { "" };
+                    let text = format!(
+                        "{} <b>{}</b> - {}\n{}{}\nPID: {}\n<pre>{}</pre>",
+                        status.emoji(),
+                        html::escape(unit),
+                        html::escape(&status.description),
+                        restarted,
+                        status.summary(),
+                        status.main_pid,
+                        html::escape(&journal)
+                    );
+                    send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(text)).await?;
+                }
+                _ => {
+                    bot.send_message(msg.chat.id, "Usage: /svc list | status <unit> | restart <unit>").await?;
+                }
+            }
+        }
+        Command::Docker => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let args: Vec<&str> = args.split_whitespace().skip(1).collect();
+            let name = args.get(1).copied();
+            if let Some(name) = name
+                && !docker::allowed(name)
+            {
+                bot.send_message(msg.chat.id, format!("Container {} is not in the allowlist.", name)).await?;
+                return Ok(());
+            }
+            let reply = match (args.first().copied(), name) {
+                (Some("ps") | None, _) => docker_ps().await,
+                (Some("stats"), _) => docker_stats().await,
+                (Some("logs"), Some(name)) => {
+                    let lines = args.get(2).and_then(|n| n.parse::<usize>().ok()).unwrap_or(CONFIG.docker.log_lines);
+                    match docker::logs(name, lines.min(logs::MAX_TAIL)).await {
+                        Ok(output) => {
+                            let title = format!("{} (last {} lines)", name, lines);
+                            return send_output(&bot, &msg, &title, output).await;
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                (Some("restart"), Some(name)) => {
+                    let user_id = msg.from.as_ref().map(|u| u.id.0).unwrap_or_default();
+                    if !allowed(user_id, "docker restart") {
+                        bot.send_message(msg.chat.id, "You are not authorized to restart containers.").await?;
+                        return Ok(());
+                    }
+                    info!("[bot] restarting container {}", name);
+                    match docker::restart(name).await {
+                        Ok(()) => docker::inspect(name).await.map(|inspect| {
+                            format!(
+                                "🔄 <b>{}</b> restarted, {} restarts in total",
+                                html::escape(name),
+                                inspect.restart_count
+                            )
+                        }),
+                        Err(e) => Err(e),
+                    }
+                }
+                _ => Err("Usage: /docker ps | stats | logs <name> [lines] | restart <name>".to_string()),
+            };
+            match reply {
+                Ok(text) => {
+                    send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(text)).await?;
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, e).await?;
+                }
+            }
+        }
+        Command::Checks => {
+            send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(checks_status())).await?;
+        }
+        Command::Certs => {
+            send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(certs_status())).await?;
+        }
+        Command::Df => {
+            let text = msg.text().unwrap_or_default().to_string();
+            let path = text.split_whitespace().nth(1).map(str::to_string);
+            let reply = tokio::task::spawn_blocking(move || df(path.as_deref()))
+                .await
+                .unwrap_or_else(|e| Err(e.to_string()));
+            match reply {
+                Ok(text) => {
+                    send_rendered(&bot, msg.chat.id, msg.thread_id, None, &html(text)).await?;
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, e).await?;
+                }
+            }
+        }
+        Command::Graph => {
+            let args = msg.text().unwrap_or_default().to_string();
+            let mut args = args.split_whitespace().skip(1);
+            let metric = args.next().unwrap_or("cpu");
+            let range = args.next().map_or(Some(chrono::Duration::hours(6)), parse_duration);
+            let Some(range) = range.filter(|_| monitor::METRICS.contains(&metric)) else {
+                bot.send_message(msg.chat.id, format!("Usage: /graph <{}> [6h]", monitor::METRICS.join("|")))
+                    .await?;
+                return Ok(());
+            };
+            if !CONFIG.history.enable {
+                bot.send_message(msg.chat.id, "Metrics history is disabled, set [history] enable = true.")
+                    .await?;
+                return Ok(());
+            }
+            if range > history::retention() {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("Range is longer than the retention of {}.", format_duration(history::retention())),
+                )
+                .await?;
+                return Ok(());
+            }
+            let series = history::series(metric, range);
+            let title = format!("{} {} · last {}", HOSTNAME.as_str(), metric, format_duration(range));
+            let unit = monitor::unit(metric).trim().to_string();
+            let chart = tokio::task::spawn_blocking(move || history::chart(&title, &unit, &series))
+                .await
+                .unwrap_or_else(|e| Err(e.to_string()));
+            match chart {
+                Ok(png) => {
+                    let photo = InputFile::memory(png).file_name(format!("{}.png", metric));
+                    let mut req = bot.send_photo(msg.chat.id, photo);
+                    req.message_thread_id = msg.thread_id;
+                    req.await?;
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("Cannot draw {}: {}", metric, e)).await?;
+                }
+            }
+        }
         // Command::Mails => {
         //     let history = EMAIL_HISTORY.lock().unwrap();
         //     let mut response = String::from("<b>邮件历史记录：</b>\n\n");
@@ -178,23 +598,182 @@ pub async fn answer(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()>
     Ok(())
 }
 
-fn run_shell(cmd: String) -> String {
-    use std::process::Command;
+/// One line per uptime check with its state since the last change
+pub fn checks_status() -> String {
+    if CONFIG.check.is_empty() {
+        return "No check configured.".to_string();
+    }
+    let status = checks::STATUS.lock().unwrap();
+    let now = Local::now();
+    let mut text = String::new();
+    for check in &CONFIG.check {
+        let line = match status.get(&check.name) {
+            None => format!("⚪ <b>{}</b>: pending", html::escape(&check.name)),
+            Some(s) if s.ok => format!(
+                "🟢 <b>{}</b>: up {}, {} ms",
+                html::escape(&check.name),
+                format_duration(now - s.since),
+                s.latency.as_millis()
+            ),
+            Some(s) => format!(
+                "{} <b>{}</b>: down {}, {} failures\n<i>{}</i>",
+                if s.firing { "🔴" } else { "🟡" },
+                html::escape(&check.name),
+                format_duration(now - s.since),
+                s.consecutive_failures,
+                html::escape(s.error.as_deref().unwrap_or_default())
+            ),
+        };
+        text.push_str(&line);
+        text.push('\n');
+    }
+    text
+}
 
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg(cmd)
-        .output()
-        .expect("Failed to execute command");
+/// Table of the mounted filesystems like `df -h`, only the one holding `path` when given
+fn df(path: Option<&str>) -> Result<String, String> {
+    let mounts = disk::mounts();
+    let mounts = match path {
+        Some(path) => vec![disk::find(&mounts, path).ok_or_else(|| format!("No filesystem holds {}", path))?],
+        None => mounts.iter().collect(),
+    };
+    if mounts.is_empty() {
+        return Err("No filesystem found.".to_string());
+    }
+    let width = mounts.iter().map(|m| m.mount.chars().count()).max().unwrap_or(0).max(5);
+    let mut table = format!(
+        "{:<width$} {:>6} {:>6} {:>6} {:>4} {:>5}\n",
+        "Mount", "Size", "Used", "Avail", "Use%", "IUse%"
+    );
+    for m in mounts {
+        let inodes = m.inode_percent().map_or("-".to_string(), |p| format!("{:.0}%", p));
+        table.push_str(&format!(
+            "{:<width$} {:>6} {:>6} {:>6} {:>3.0}% {:>5}  {}\n",
+            m.mount,
+            disk::human(m.total),
+            disk::human(m.used),
+            disk::human(m.available),
+            m.percent(),
+            inodes,
+            m.fs
+        ));
+    }
+    Ok(format!("<b>{}</b>\n<pre>{}</pre>", html::escape(HOSTNAME.as_str()), html::escape(&table)))
+}
 
-    if output.status.success() {
-        String::from_utf8_lossy(&output.stdout).to_string()
-    } else {
-        String::from_utf8_lossy(&output.stderr).to_string()
+/// Certificates sorted by expiry, unreadable targets last
+fn certs_status() -> String {
+    let status = certs::STATUS.lock().unwrap();
+    if status.is_empty() {
+        return "No certificate checked yet.".to_string();
     }
+    let mut valid = vec![];
+    let mut failed = vec![];
+    for (target, result) in status.iter() {
+        match result {
+            Ok(list) => valid.extend(list.iter().map(|cert| (target, cert))),
+            Err(e) => failed.push(format!("⚪ <b>{}</b>: {}", html::escape(target), html::escape(e))),
+        }
+    }
+    valid.sort_by_key(|(_, cert)| cert.not_after);
+    let lines = valid.into_iter().map(|(target, cert)| {
+        let days = cert.days_left();
+        let emoji = match days {
+            ..7 => "🔴",
+            7..30 => "🟡",
+            _ => "🟢",
+        };
+        format!(
+            "{} <b>{}</b>: {} days, {}\n<i>{}</i>",
+            emoji,
+            html::escape(target),
+            days,
+            cert.not_after.format("%Y-%m-%d"),
+            html::escape(&cert.subject)
+        )
+    });
+    lines.chain(failed).collect::<Vec<_>>().join("\n")
+}
+
+/// Allowlisted containers with their state, health and restart count
+async fn docker_ps() -> Result<String, String> {
+    let containers = docker::list().await?;
+    if containers.is_empty() {
+        return Ok("No allowlisted container found.".to_string());
+    }
+    let mut text = String::new();
+    for container in containers {
+        let restarts = docker::inspect(&container.id).await.map(|i| i.restart_count).unwrap_or(0);
+        text.push_str(&format!(
+            "{} <b>{}</b>: {}\n<i>{}</i>{}\n",
+            container.emoji(),
+            html::escape(container.name()),
+            html::escape(&container.status),
+            html::escape(&container.image),
+            if restarts > 0 { format!(" · {} restarts", restarts) } else { String::new() }
+        ));
+    }
+    Ok(text)
+}
+
+async fn docker_stats() -> Result<String, String> {
+    let running: Vec<_> = docker::list().await?.into_iter().filter(|c| c.state == "running").collect();
+    if running.is_empty() {
+        return Ok("No allowlisted container running.".to_string());
+    }
+    let stats = futures::future::join_all(running.iter().map(|c| docker::stats(&c.id))).await;
+    let mut text = String::new();
+    for (container, stats) in running.iter().zip(stats) {
+        let line = match stats {
+            Ok(s) => format!(
+                "CPU {:.1}% · Mem {:.1} MB / {:.1} MB",
+                s.cpu_percent,
+                s.memory as f64 / 1_048_576.0,
+                s.memory_limit as f64 / 1_048_576.0
+            ),
+            Err(e) => html::escape(&e),
+        };
+        text.push_str(&format!("<b>{}</b>: {}\n", html::escape(container.name()), line));
+    }
+    Ok(text)
+}
+
+/// File of a log alias, replying with the known aliases when missing or unknown
+async fn log_file(bot: &Bot, msg: &Message, alias: Option<&str>) -> ResponseResult<Option<PathBuf>> {
+    if let Some(path) = alias.and_then(logs::resolve) {
+        return Ok(Some(path));
+    }
+    let known = logs::aliases().into_keys().collect::<Vec<_>>().join(", ");
+    let text = match alias {
+        Some(alias) => format!("Unknown or missing log {}, available: {}", alias, known),
+        None => format!("Please provide a log, available: {}", known),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(None)
+}
+
+/// Send command output as preformatted text, or as a document when too long
+async fn send_output(bot: &Bot, msg: &Message, title: &str, output: String) -> ResponseResult<()> {
+    let limit = CONFIG.telegram.document_threshold.unwrap_or(4 * CONFIG.telegram.max_message_len);
+    if tg_len(&output) > limit {
+        let caption = html(format!("<b>{}</b>\n(sent as document)", html::escape(title)));
+        send_rendered(bot, msg.chat.id, msg.thread_id, None, &caption).await?;
+        send_document(bot, msg.chat.id, msg.thread_id, "output.txt", output).await?;
+        return Ok(());
+    }
+    let text = format!("<b>{}</b>\n<pre>{}</pre>", html::escape(title), html::escape(&output));
+    send_rendered(bot, msg.chat.id, msg.thread_id, None, &html(text)).await?;
+    Ok(())
 }
 
-fn metric() -> String {
+fn html(text: String) -> Rendered {
+    Rendered {
+        text,
+        format: TemplateFormat::Html,
+    }
+}
+
+pub fn metric() -> String {
     // keyword -> value
     let mut metrics: Vec<(&str, String)> = vec![];
     let mut system = System::new_all();
@@ -217,8 +796,8 @@ fn metric() -> String {
 
     let network = Networks::new_with_refreshed_list();
     // 总网络IO
-    let total_received: u64 = network.iter().map(|(_, data)| data.received()).sum();
-    let total_transmitted: u64 = network.iter().map(|(_, data)| data.transmitted()).sum();
+    let total_received: u64 = network.values().map(|data| data.received()).sum();
+    let total_transmitted: u64 = network.values().map(|data| data.transmitted()).sum();
     let network_io = format!(
         "Rx {:.2} MB, Tx {:.2} MB",
         total_received as f64 / 1_048_576.0,    // 转换
@@ -226,19 +805,25 @@ fn metric() -> String {
     );
     metrics.push(("Net", network_io));
 
-    // 一个磁盘的使用情况
-    let disk_info = if let Some(disk) = Disks::new_with_refreshed_list().get(0) {
-        let used = (disk.total_space() - disk.available_space()) as f64;
-        let total = disk.total_space() as f64;
-        let percent = used / total * 100.0;
-        format!(
-            "{:.2} GB / {:.2} GB ({:.2}%)",
-            used / 1_073_741_824.0,         // 转换为GB
-            total as f64 / 1_073_741_824.0, // 转换为GB
-            percent
-        )
-    } else {
+    // 每个文件系统的使用情况
+    let mounts = disk::mounts();
+    let disk_info = if mounts.is_empty() {
         " No disk information available".to_string()
+    } else {
+        mounts
+            .iter()
+            .map(|m| {
+                let inodes = m.inode_percent().map(|p| format!(", inodes {:.0}%", p)).unwrap_or_default();
+                format!(
+                    "\n  {} {:.2} GB / {:.2} GB ({:.2}%{})",
+                    html::escape(&m.mount),
+                    m.used as f64 / 1_073_741_824.0, // 转换为GB
+                    (m.used + m.available) as f64 / 1_073_741_824.0,
+                    m.percent(),
+                    inodes
+                )
+            })
+            .collect()
     };
     metrics.push(("Disk", disk_info));
 
@@ -269,21 +854,14 @@ fn metric() -> String {
     let kw = format!("{}@{}", pname, pid);
     metrics.push((&kw, process_memory_usage));
 
-    return metrics
+    metrics
         .iter()
         .map(|(k, v)| format!("<b>{}</b>: {}", k, v))
         .collect::<Vec<String>>()
-        .join("\n");
-}
-
-fn get_hostname() -> String {
-    std::process::Command::new("hostname")
-        .output()
-        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
-        .unwrap_or_else(|_| "unknown".to_string())
+        .join("\n")
 }
 
-fn top() -> String {
+pub fn top() -> String {
     let mut system = System::new_all();
     system.refresh_all();
     let mut processes: Vec<_> = system.processes().iter().collect();
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use log::info;
+use once_cell::sync::Lazy;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode, ThreadId};
+use teloxide::utils::html;
+
+use crate::audit::{self, AuditEvent};
+use crate::bot::auth::{command_name, role_of};
+use crate::bot::callback::user_name;
+use crate::bot::command::{run, Command};
+use crate::bot::shell;
+use crate::config::{Runbook, CONFIG};
+use crate::runbook;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static PENDING: Lazy<Mutex<HashMap<u64, Pending>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// What runs once confirmed
+#[derive(Debug, Clone)]
+enum Job {
+    Command(Box<Message>, Command),
+    Runbook {
+        thread_id: Option<ThreadId>,
+        user: String,
+        runbook: &'static Runbook,
+        params: BTreeMap<String, String>,
+    },
+}
+
+impl Job {
+    fn name(&self) -> String {
+        match self {
+            Job::Command(_, cmd) => command_name(cmd),
+            Job::Runbook { .. } => "run".to_string(),
+        }
+    }
+}
+
+/// A destructive command waiting for its author to confirm
+#[derive(Debug, Clone)]
+struct Pending {
+    job: Job,
+    /// what is being confirmed, as shown to the user
+    text: String,
+    chat_id: ChatId,
+    user_id: u64,
+    prompt: MessageId,
+    expires: Instant,
+}
+
+/// Command words without the slash and bot name, e.g. `svc restart`
+fn command_words(msg: &Message) -> Vec<String> {
+    msg.text()
+        .unwrap_or_default()
+        .trim_start_matches('/')
+        .split_whitespace()
+        .enumerate()
+        .map(|(i, w)| if i == 0 { w.split('@').next().unwrap_or_default() } else { w })
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// Whether the message matches an entry of `confirm_commands`, like `exit` or `svc restart`.
+/// Runbooks the user may not run are refused right away instead of asking first
+pub fn required(msg: &Message) -> bool {
+    let words = command_words(msg);
+    if words.first().is_some_and(|w| w == "run")
+        && let Some(rb) = words.get(1).and_then(|name| runbook::find(name))
+        && rb.confirm
+    {
+        let user_id = msg.from.as_ref().map(|u| u.id.0).unwrap_or_default();
+        return role_of(user_id) >= rb.role;
+    }
+    CONFIG.telegram.confirm_commands.iter().any(|entry| {
+        let entry: Vec<&str> = entry.split_whitespace().collect();
+        !entry.is_empty() && entry.len() <= words.len() && entry.iter().zip(&words).all(|(e, w)| e == w)
+    })
+}
+
+/// Reply with Confirm / Cancel buttons, the command runs once its author confirms
+pub async fn ask(bot: Bot, msg: Message, cmd: Command) -> ResponseResult<()> {
+    let user_id = msg.from.as_ref().map(|u| u.id.0).unwrap_or_default();
+    let text = msg.text().unwrap_or_default().to_string();
+    let (chat_id, thread_id) = (msg.chat.id, msg.thread_id);
+    let job = Job::Command(Box::new(msg), cmd);
+    prompt(bot, chat_id, thread_id, user_id, text, job).await
+}
+
+/// Confirm / Cancel buttons for a runbook started from an alert button
+pub async fn ask_runbook(
+    bot: Bot,
+    chat_id: ChatId,
+    thread_id: Option<ThreadId>,
+    user_id: u64,
+    user: String,
+    runbook: &'static Runbook,
+    params: BTreeMap<String, String>,
+) -> ResponseResult<()> {
+    let text = format!("/run {}", runbook::argv(runbook, &params).join(" "));
+    let job = Job::Runbook {
+        thread_id,
+        user,
+        runbook,
+        params,
+    };
+    prompt(bot, chat_id, thread_id, user_id, text, job).await
+}
+
+async fn prompt(
+    bot: Bot,
+    chat_id: ChatId,
+    thread_id: Option<ThreadId>,
+    user_id: u64,
+    text: String,
+    job: Job,
+) -> ResponseResult<()> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let timeout = Duration::from_secs(CONFIG.telegram.confirm_timeout_secs);
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("✅ Confirm", format!("confirm:{}", id)),
+        InlineKeyboardButton::callback("✖️ Cancel", format!("cancel:{}", id)),
+    ]]);
+    let question = format!(
+        "⚠️ Confirm <code>{}</code> within {}s?",
+        html::escape(&text),
+        timeout.as_secs()
+    );
+    let mut req = bot.send_message(chat_id, question).parse_mode(ParseMode::Html);
+    req.message_thread_id = thread_id;
+    req.reply_markup = Some(keyboard.into());
+    let prompt = req.await?;
+
+    PENDING.lock().unwrap().insert(
+        id,
+        Pending {
+            job,
+            text,
+            chat_id,
+            user_id,
+            prompt: prompt.id,
+            expires: Instant::now() + timeout,
+        },
+    );
+
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        let expired = PENDING.lock().unwrap().remove(&id);
+        if let Some(pending) = expired {
+            info!("[confirm] {} expired", pending.text);
+            let _ = bot.edit_message_text(chat_id, pending.prompt, "⌛ Expired, command not executed.").await;
+        }
+    });
+    Ok(())
+}
+
+/// Handle the Confirm / Cancel buttons
+pub async fn handle(bot: Bot, q: CallbackQuery, action: &str, id: u64) -> ResponseResult<()> {
+    let user = user_name(&q.from);
+    let (pending, other_user) = {
+        let mut pending = PENDING.lock().unwrap();
+        match pending.get(&id) {
+            Some(p) if p.user_id != q.from.id.0 => (None, true),
+            _ => (pending.remove(&id), false),
+        }
+    };
+    if other_user {
+        bot.answer_callback_query(q.id)
+            .text("Only the user who sent the command can confirm it.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+    let Some(pending) = pending.filter(|p| p.expires > Instant::now()) else {
+        bot.answer_callback_query(q.id).text("Expired").await?;
+        return Ok(());
+    };
+
+    let chat_id = pending.chat_id;
+    let text = pending.text.clone();
+    audit::record(AuditEvent::new(action, chat_id.0, q.from.id.0, &user, &pending.job.name()).detail(text.clone()));
+    if action == "confirm" {
+        bot.answer_callback_query(q.id).text("Confirmed").await?;
+        bot.edit_message_text(chat_id, pending.prompt, format!("✅ Confirmed by {}: {}", user, text))
+            .await?;
+        info!("[confirm] {} confirmed {}", user, text);
+        match pending.job {
+            Job::Command(msg, cmd) => run(bot, *msg, cmd).await,
+            Job::Runbook {
+                thread_id,
+                user,
+                runbook,
+                params,
+            } => {
+                shell::spawn_runbook(bot, chat_id, thread_id, user, runbook, params);
+                Ok(())
+            }
+        }
+    } else {
+        bot.answer_callback_query(q.id).text("Cancelled").await?;
+        bot.edit_message_text(chat_id, pending.prompt, format!("✖️ Cancelled by {}: {}", user, text))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use crate::config::TemplateFormat;
+
+/// Telegram counts message length in utf-16 code units
+pub fn tg_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// Split a message into chunks of at most `limit` utf-16 units.
+/// Prefers line boundaries, and closes/reopens html tags or markdown code fences across chunks.
+pub fn split_message(text: &str, format: TemplateFormat, limit: usize) -> Vec<String> {
+    let limit = limit.max(64);
+    // room left for closing tags at the end of a chunk
+    let budget = limit - (limit / 4).min(256);
+    let mut chunks = vec![];
+    let mut chunk = String::new();
+    let mut open: Vec<(String, String)> = vec![];
+
+    for line in text.split_inclusive('\n') {
+        for piece in hard_split(line, format, budget) {
+            let closing = close_tags(&open, format);
+            if !chunk.is_empty() && tg_len(&chunk) + tg_len(piece) + tg_len(&closing) > limit {
+                chunk.push_str(&closing);
+                chunks.push(std::mem::take(&mut chunk));
+                chunk = reopen_tags(&open, format);
+            }
+            chunk.push_str(piece);
+            track_tags(&mut open, piece, format);
+        }
+    }
+    if !chunk.trim().is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Cut a single over-long line, never inside an html tag/entity or after a markdown escape
+fn hard_split(line: &str, format: TemplateFormat, budget: usize) -> Vec<&str> {
+    let mut pieces = vec![];
+    let mut rest = line;
+    while tg_len(rest) > budget {
+        let mut cut = 0;
+        let mut units = 0;
+        for (i, c) in rest.char_indices() {
+            units += c.len_utf16();
+            if units > budget {
+                break;
+            }
+            cut = i + c.len_utf8();
+        }
+        let head = &rest[..cut];
+        let safe = match format {
+            TemplateFormat::Html => {
+                let tag = head.rfind('<').filter(|&i| !head[i..].contains('>'));
+                let entity = head.rfind('&').filter(|&i| !head[i..].contains(';'));
+                tag.into_iter().chain(entity).min().unwrap_or(cut)
+            }
+            TemplateFormat::Markdown if head.ends_with('\\') => cut - 1,
+            _ => cut,
+        };
+        let cut = if safe == 0 { cut } else { safe };
+        pieces.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+fn track_tags(open: &mut Vec<(String, String)>, piece: &str, format: TemplateFormat) {
+    match format {
+        TemplateFormat::Html => {
+            let mut rest = piece;
+            while let Some(start) = rest.find('<') {
+                let Some(end) = rest[start..].find('>') else {
+                    break;
+                };
+                let tag = &rest[start..start + end + 1];
+                rest = &rest[start + end + 1..];
+                let inner = tag[1..tag.len() - 1].trim();
+                if let Some(name) = inner.strip_prefix('/') {
+                    let name = name.trim().to_lowercase();
+                    if let Some(pos) = open.iter().rposition(|(n, _)| *n == name) {
+                        open.remove(pos);
+                    }
+                } else if !inner.ends_with('/') {
+                    let name = inner.split_whitespace().next().unwrap_or_default().to_lowercase();
+                    open.push((name, tag.to_string()));
+                }
+            }
+        }
+        TemplateFormat::Markdown => {
+            if piece.trim_start().starts_with("```") {
+                if open.is_empty() {
+                    open.push(("```".to_string(), piece.trim_end().to_string()));
+                } else {
+                    open.clear();
+                }
+            }
+        }
+        TemplateFormat::Text => {}
+    }
+}
+
+fn close_tags(open: &[(String, String)], format: TemplateFormat) -> String {
+    match format {
+        TemplateFormat::Html => open.iter().rev().map(|(name, _)| format!("</{}>", name)).collect(),
+        TemplateFormat::Markdown if !open.is_empty() => "\n```".to_string(),
+        _ => String::new(),
+    }
+}
+
+fn reopen_tags(open: &[(String, String)], format: TemplateFormat) -> String {
+    match format {
+        TemplateFormat::Html => open.iter().map(|(_, tag)| tag.as_str()).collect(),
+        TemplateFormat::Markdown => open.iter().map(|(_, fence)| format!("{}\n", fence)).collect(),
+        TemplateFormat::Text => String::new(),
+    }
+}
+
+/// Plain text of a html document, e.g. mail bodies
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim().to_lowercase();
+        rest = &rest[start + end + 1..];
+        let name = tag.trim_start_matches('/').split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default();
+        match name {
+            "br" | "p" | "div" | "tr" | "li" | "h1" | "h2" | "h3" | "h4" | "table" => text.push('\n'),
+            "td" | "th" => text.push('\t'),
+            "style" | "script" | "head" if !tag.starts_with('/') => {
+                let close = format!("</{}", name);
+                rest = rest.find(&close).map(|i| &rest[i..]).unwrap_or("");
+            }
+            _ => {}
+        }
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    // collapse blank lines
+    let mut lines: Vec<&str> = vec![];
+    for line in text.lines().map(|l| l.trim_end()) {
+        if line.trim().is_empty() && lines.last().is_none_or(|l| l.trim().is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_length() {
+        assert_eq!(tg_len("abc"), 3);
+        assert_eq!(tg_len("中文"), 2);
+        assert_eq!(tg_len("🚨"), 2);
+    }
+
+    #[test]
+    fn short_message_is_one_chunk() {
+        assert_eq!(split_message("hello\nworld", TemplateFormat::Text, 4096), ["hello\nworld"]);
+        assert!(split_message("  \n", TemplateFormat::Text, 4096).is_empty());
+    }
+
+    #[test]
+    fn chunks_respect_the_limit() {
+        let text = "line of text\n".repeat(100);
+        let chunks = split_message(&text, TemplateFormat::Text, 100);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| tg_len(c) <= 100));
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn html_tags_are_reopened() {
+        let text = format!("<pre>{}</pre>", "0123456789\n".repeat(30));
+        let chunks = split_message(&text, TemplateFormat::Html, 100);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(tg_len(chunk) <= 100);
+            assert_eq!(chunk.matches("<pre>").count(), chunk.matches("</pre>").count(), "{}", chunk);
+        }
+    }
+
+    #[test]
+    fn markdown_fences_are_reopened() {
+        let text = format!("```\n{}```\n", "0123456789\n".repeat(30));
+        let chunks = split_message(&text, TemplateFormat::Markdown, 100);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.matches("```").count() % 2 == 0));
+    }
+
+    #[test]
+    fn long_line_is_not_cut_inside_an_entity() {
+        let line = "&amp;".repeat(40);
+        for piece in hard_split(&line, TemplateFormat::Html, 64) {
+            assert!(piece.starts_with('&') && piece.ends_with(';'), "{}", piece);
+        }
+    }
+
+    #[test]
+    fn html_to_plain_text() {
+        let html = "<html><head><style>p{}</style></head><body><p>a &amp; b</p><br><p>c</p></body></html>";
+        assert_eq!(html_to_text(html), "a & b\n\nc");
+    }
+}
@@ -1,4 +1,10 @@
+mod auth;
+mod callback;
 mod command;
+mod confirm;
+mod shell;
+pub mod format;
+pub mod report;
 use std::thread;
 
 use log::info;
@@ -7,9 +13,19 @@ pub mod telegram;
 pub mod traits;
 use crossbeam::channel::{Sender, bounded};
 
-pub static BOTS_TX: OnceCell<Sender<String>> = OnceCell::new();
+use crate::alert::Alert;
+use crate::incident::Incident;
+
+pub static BOTS_TX: OnceCell<Sender<Alert>> = OnceCell::new();
 pub static STATUS: OnceCell<telegram::TGStatus> = OnceCell::new();
+pub static BOT: OnceCell<teloxide::Bot> = OnceCell::new();
 
+/// Update the telegram messages of an incident changed outside of the bot
+pub async fn refresh(incident: &Incident) {
+    if let Some(bot) = BOT.get() {
+        callback::refresh(bot, incident).await;
+    }
+}
 
 pub fn run_bots() {
     let (tx, rx) = bounded(64);
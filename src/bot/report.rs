@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+use chrono::{Duration, Local};
+use log::{error, info};
+use teloxide::types::{MessageId, ThreadId};
+use teloxide::utils::html;
+
+use crate::bot::command::{checks_status, metric, top};
+use crate::bot::telegram::send_rendered;
+use crate::bot::{BOT, STATUS};
+use crate::config::{ChatItem, Report, TemplateFormat, CONFIG};
+use crate::heartbeat::{self, BEATS};
+use crate::incident::{self, IncidentStatus};
+use crate::template::Rendered;
+use crate::util::{format_duration, parse_cron, sleep_until_next};
+use crate::G_TOKIO_RUNTIME;
+
+const SECTIONS: [&str; 6] = ["uptime", "metrics", "top", "alerts", "checks", "heartbeats"];
+
+pub fn validate() -> anyhow::Result<()> {
+    for report in &CONFIG.report {
+        if parse_cron(&report.schedule).is_none() {
+            bail!("[report] invalid schedule `{}` of {}", report.schedule, report.name);
+        }
+        if let Some(section) = report.sections.iter().find(|s| !SECTIONS.contains(&s.as_str())) {
+            bail!("[report] unknown section `{}` of {}, expect one of {:?}", section, report.name, SECTIONS);
+        }
+    }
+    Ok(())
+}
+
+/// Alerts of the last 24h by severity and how many are still open
+fn alerts_summary() -> String {
+    let since = Local::now() - Duration::hours(24);
+    let recent: Vec<_> = incident::list()
+        .into_iter()
+        .filter(|i| i.alert.time > since && !i.alert.is_recovery())
+        .collect();
+    if recent.is_empty() {
+        return "no alert".to_string();
+    }
+    let mut by_severity: BTreeMap<_, usize> = BTreeMap::new();
+    for incident in &recent {
+        *by_severity.entry(incident.alert.severity.as_str()).or_default() += 1;
+    }
+    let open = recent.iter().filter(|i| i.status != IncidentStatus::Resolved).count();
+    let counts: Vec<String> = by_severity.iter().map(|(s, n)| format!("{} {}", n, s)).collect();
+    format!("{} alerts ({}), {} still open", recent.len(), counts.join(", "), open)
+}
+
+fn heartbeats_summary() -> String {
+    let now = Local::now();
+    let beats = BEATS.lock().unwrap();
+    CONFIG
+        .heartbeat
+        .iter()
+        .filter_map(|hb| {
+            let beat = beats.get(&hb.name)?;
+            let last = beat
+                .last
+                .map(|last| format!("{} ago", format_duration(now - last)))
+                .unwrap_or("never".to_string());
+            let emoji = if beat.missed { "🔴" } else { "🟢" };
+            let next = match beat.missed {
+                true => String::new(),
+                false => format!(", due in {}", format_duration(heartbeat::deadline(hb, beat) - now)),
+            };
+            Some(format!("{} <b>{}</b>: last ping {}{}", emoji, html::escape(&hb.name), last, next))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn render(report: &Report) -> String {
+    let mut parts = vec![format!("📊 <b>{}</b> · {}", html::escape(&report.name), Local::now().format("%Y-%m-%d %H:%M"))];
+    for section in &report.sections {
+        let body = match section.as_str() {
+            "uptime" => STATUS
+                .get()
+                .map(|s| format!("<b>Uptime</b>: {}", format_duration(Local::now() - s.start_at)))
+                .unwrap_or_default(),
+            "metrics" => metric(),
+            "top" => top().trim_end().to_string(),
+            "alerts" => format!("<b>Alerts 24h</b>: {}", alerts_summary()),
+            "checks" if !CONFIG.check.is_empty() => format!("<b>Checks</b>\n{}", checks_status().trim_end()),
+            "heartbeats" if !CONFIG.heartbeat.is_empty() => format!("<b>Heartbeats</b>\n{}", heartbeats_summary()),
+            _ => String::new(),
+        };
+        if !body.is_empty() {
+            parts.push(body);
+        }
+    }
+    parts.join("\n\n")
+}
+
+async fn post(report: &'static Report) {
+    let Some(bot) = BOT.get() else {
+        return;
+    };
+    // metrics and top refresh every process through sysinfo, keep it off the async workers
+    let text = match tokio::task::spawn_blocking(move || render(report)).await {
+        Ok(text) => text,
+        Err(e) => {
+            error!("[report] failed to render {}: {}", report.name, e);
+            return;
+        }
+    };
+    let rendered = Rendered {
+        text,
+        format: TemplateFormat::Html,
+    };
+    let chats: &Vec<ChatItem> = match report.chats.is_empty() {
+        true => &CONFIG.telegram.allow_chat_id,
+        false => &report.chats,
+    };
+    for chat in chats {
+        let thread_id = chat.thread_id().map(|id| ThreadId(MessageId(id)));
+        if let Err(e) = send_rendered(bot, chat.chat_id().to_string(), thread_id, None, &rendered).await {
+            error!("[report] failed to send {} to {}: {}", report.name, chat.chat_id(), e);
+        }
+    }
+}
+
+pub fn run_reports() {
+    for report in &CONFIG.report {
+        let Some(schedule) = parse_cron(&report.schedule) else {
+            continue;
+        };
+        info!("[report] {} on `{}`", report.name, report.schedule);
+        G_TOKIO_RUNTIME.spawn(async move {
+            while sleep_until_next(&schedule).await {
+                info!("[report] posting {}", report.name);
+                post(report).await;
+            }
+        });
+    }
+}
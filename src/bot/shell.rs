@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{error, info, warn};
+use teloxide::prelude::*;
+use teloxide::types::{ParseMode, ThreadId};
+use teloxide::utils::html;
+use tokio::process::Command;
+
+use crate::bot::format::tg_len;
+use crate::bot::telegram::send_document;
+use crate::alert::HOSTNAME;
+use crate::config::{Runbook, CONFIG};
+use crate::exec::{self, ExecResult};
+use crate::runbook;
+
+/// `<b>user@host</b> &gt; <code>cmd</code>`
+pub fn prompt(user: &str, hostname: &str, cmd: &str) -> String {
+    format!(
+        "<b>{}@{}</b> &gt; <code>{}</code>",
+        html::escape(user),
+        html::escape(hostname),
+        html::escape(cmd)
+    )
+}
+
+/// Escaped tail of the output fitting in `budget` utf-16 units
+fn tail(output: &str, budget: usize) -> (String, bool) {
+    let escaped = html::escape(output);
+    if tg_len(&escaped) <= budget {
+        return (escaped, false);
+    }
+    let mut take = budget;
+    loop {
+        let skip = output.chars().count().saturating_sub(take);
+        let escaped = html::escape(&output.chars().skip(skip).collect::<String>());
+        if tg_len(&escaped) <= budget || take == 0 {
+            return (escaped, true);
+        }
+        take = take * 3 / 4;
+    }
+}
+
+fn render(header: &str, output: &str, footer: &str) -> (String, bool) {
+    let budget = CONFIG.telegram.max_message_len.saturating_sub(tg_len(header) + tg_len(footer) + 64);
+    let (body, truncated) = tail(output, budget);
+    let body = if body.trim().is_empty() { "(no output)".to_string() } else { body };
+    let marker = if truncated { "…\n" } else { "" };
+    (format!("{}\n<pre>{}{}</pre>\n{}", header, marker, body, footer), truncated)
+}
+
+/// Run a command and keep a telegram message updated with its output.
+/// Output which does not fit in a message is sent as a `.txt` document at the end.
+pub async fn stream(
+    bot: Bot,
+    chat_id: ChatId,
+    thread_id: Option<ThreadId>,
+    header: String,
+    cmd: Command,
+    timeout: Duration,
+) -> ResponseResult<ExecResult> {
+    let mut req = bot.send_message(chat_id, format!("{}\n<i>running…</i>", header));
+    req.message_thread_id = thread_id;
+    let sent = req.parse_mode(ParseMode::Html).await?;
+
+    let output = Arc::new(Mutex::new(String::new()));
+    let job = exec::execute(cmd, timeout, output.clone());
+    tokio::pin!(job);
+    let mut ticker = tokio::time::interval(Duration::from_millis(CONFIG.shell.update_interval_ms.max(500)));
+    ticker.tick().await;
+    let mut shown = String::new();
+    let result = loop {
+        tokio::select! {
+            result = &mut job => break result,
+            _ = ticker.tick() => {
+                let current = output.lock().unwrap().clone();
+                if current == shown {
+                    continue;
+                }
+                let (text, _) = render(&header, &current, "<i>running…</i>");
+                if let Err(e) = bot.edit_message_text(chat_id, sent.id, text).parse_mode(ParseMode::Html).await {
+                    warn!("[shell] failed to update output: {}", e);
+                }
+                shown = current;
+            }
+        }
+    };
+
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            error!("[shell] failed to execute: {}", e);
+            let text = format!("{}\n<i>failed to execute: {}</i>", header, html::escape(&e.to_string()));
+            bot.edit_message_text(chat_id, sent.id, text).parse_mode(ParseMode::Html).await?;
+            return Ok(ExecResult {
+                output: e.to_string(),
+                code: None,
+                timed_out: false,
+                elapsed: Duration::ZERO,
+            });
+        }
+    };
+    info!("[shell] finished: {}", result.status());
+    let footer = format!("<i>{}</i>", html::escape(&result.status()));
+    let (text, truncated) = render(&header, &result.output, &footer);
+    bot.edit_message_text(chat_id, sent.id, text).parse_mode(ParseMode::Html).await?;
+    if truncated {
+        send_document(&bot, chat_id, thread_id, "output.txt", result.output.clone()).await?;
+    }
+    Ok(result)
+}
+
+/// Run a runbook in the background, streaming its output to the chat
+pub fn spawn_runbook(
+    bot: Bot,
+    chat_id: ChatId,
+    thread_id: Option<ThreadId>,
+    user: String,
+    rb: &'static Runbook,
+    params: BTreeMap<String, String>,
+) {
+    let argv = runbook::argv(rb, &params).join(" ");
+    info!("[shell] runbook {} by {}: {}", rb.name, user, argv);
+    let header = format!("📘 <b>{}</b>\n{}", html::escape(&rb.name), prompt(&user, &HOSTNAME, &argv));
+    let cmd = runbook::command(rb, &params);
+    tokio::spawn(async move {
+        if let Err(e) = stream(bot, chat_id, thread_id, header, cmd, runbook::timeout(rb)).await {
+            error!("[shell] runbook {} failed: {}", rb.name, e);
+        }
+    });
+}
@@ -1,79 +1,155 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Utc};
 use crossbeam::channel::Receiver;
-use log::{error, info};
+use log::{error, info, warn};
 use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardMarkup, InputFile, MessageId, ParseMode, Recipient, ReplyMarkup, ThreadId};
+use teloxide::{ApiError, RequestError};
 
 use tokio::spawn;
 
-use crate::{bot::STATUS, config::CONFIG};
+use crate::alert::Alert;
+use crate::config::{ChatItem, TelegramRoute, TemplateFormat};
+use crate::oncall;
+use crate::template::{self, Rendered};
+use crate::{bot::{BOT, STATUS}, config::CONFIG};
 use crate::bot::command::{Command, answer};
+use crate::bot::auth::{allowed_chat, authorized};
+use crate::bot::callback::{callback, keyboard};
+use crate::bot::format::split_message;
+use crate::incident::{self, SentMessage};
+
+const PREVIEW_LEN: usize = 1024;
 
 
 #[derive(Debug)]
 pub struct TGStatus {
     pub start_at: DateTime<Local>,
-    pub admin_chat_id: Vec<String>,
+}
+
+impl Default for TGStatus {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TGStatus {
     pub fn new() -> Self {
         let start_at = Local::now();
-        TGStatus {
-            start_at,
-            admin_chat_id: vec!["768449054".into()],
-        }
+        TGStatus { start_at }
     }
 }
 
 #[derive(Debug)]
 pub struct TelegramBot {
     bot: Bot,
-    rx: Receiver<String>,
+    rx: Receiver<Alert>,
 }
 
 impl TelegramBot {
-    pub fn new(rx: Receiver<String>) -> Self {
+    pub fn new(rx: Receiver<Alert>) -> Self {
         let bot = Bot::from_env();
+        let _ = BOT.set(bot.clone());
         TelegramBot { bot, rx }
     }
 
     pub async fn run(&self) {
         let dt = chrono::Local::now();
-        self.boardcast(format!("Hello botte! {:?}", dt)).await;
+        self.boardcast_text(&format!("Hello botte! {:?}", dt)).await;
         STATUS.set(TGStatus::new()).unwrap();
         println!("[bot] botte run");
         let b = self.bot.clone();
 
         spawn(async {
-            Command::repl(b, answer).await;
+            let handler = dptree::entry()
+                .branch(
+                    Update::filter_message()
+                        .filter_command::<Command>()
+                        .filter_async(allowed_chat)
+                        .filter_async(authorized)
+                        .endpoint(answer),
+                )
+                .branch(Update::filter_callback_query().endpoint(callback));
+            Dispatcher::builder(b, handler)
+                .enable_ctrlc_handler()
+                .build()
+                .dispatch()
+                .await;
         });
         self.poll().await;
     }
 
-    pub async fn boardcast(&self, msg: String) {
-        let chat_ids = &CONFIG.telegram.allow_chat_id;
-        for chat_id in chat_ids {
-            self.send_msg(chat_id.clone(), &msg).await;
+    pub async fn boardcast(&self, alert: &Alert) {
+        // very long bodies go as an attachment, the message only keeps a preview
+        let document = CONFIG
+            .telegram
+            .document_threshold
+            .filter(|threshold| alert.body.chars().count() > *threshold)
+            .map(|_| {
+                let name = format!("{}-{}.txt", alert.source, alert.time.format("%Y%m%d-%H%M%S"));
+                (name, alert.body.clone())
+            });
+        let rendered = match document {
+            Some(_) => {
+                let mut preview = alert.clone();
+                preview.body = preview.body.chars().take(PREVIEW_LEN).collect();
+                preview.body.push_str("\n... (full body attached)");
+                template::render("telegram", &preview, "")
+            }
+            None => template::render("telegram", alert, ""),
+        };
+
+        for chat in &CONFIG.telegram.allow_chat_id {
+            let chat_id = chat.chat_id().to_string();
+            let thread_id = route_thread(chat, alert);
+            let route_index = route_index(chat, alert);
+            let route = route_index.map(|i| &CONFIG.telegram.route[i]);
+            let schedule = route.and_then(|route| route.oncall.as_deref());
+            let rendered = match schedule.and_then(oncall_user) {
+                Some(user) => Rendered {
+                    text: format!("👤 {}\n{}", oncall::mention(&user, rendered.format), rendered.text),
+                    format: rendered.format,
+                },
+                None => rendered.clone(),
+            };
+            send_alert(&self.bot, chat_id.clone(), thread_id, route_index, alert, &rendered).await;
+            if let Some(schedule) = schedule
+                && route.is_some_and(|route| route.oncall_dm)
+            {
+                send_oncall_dm(&self.bot, schedule, alert, &rendered).await;
+            }
+            if let Some((name, body)) = &document
+                && let Err(e) = send_document(&self.bot, chat_id.clone(), thread_id, name, body.clone()).await
+            {
+                error!("[bot] send document to {} failed: {}", chat_id, e);
+            }
+        }
+    }
+
+    pub async fn boardcast_text(&self, msg: &str) {
+        let rendered = Rendered {
+            text: msg.to_string(),
+            format: TemplateFormat::Text,
+        };
+        for chat in &CONFIG.telegram.allow_chat_id {
+            let thread_id = chat.thread_id().map(|id| ThreadId(MessageId(id)));
+            self.send_msg(chat.chat_id().to_string(), thread_id, &rendered).await;
         }
     }
 
-    pub async fn send_msg(&self, chat_id: String, message: &str) {
-        self.bot.send_message(chat_id, message).await.unwrap();
+    pub async fn send_msg(&self, chat_id: String, thread_id: Option<ThreadId>, message: &Rendered) {
+        if let Err(e) = send_rendered(&self.bot, chat_id.clone(), thread_id, None, message).await {
+            error!("[bot] send message to {} failed: {}", chat_id, e);
+        }
     }
 
     async fn poll(&self) {
         let stream = self.rx.clone();
         loop {
             match stream.recv() {
-                Ok(msg) => {
+                Ok(alert) => {
                     // Handle the message
-                    info!("Recv: {}", msg);
-                    if let Ok(j) = serde_json::from_str::<serde_json::Value>(&msg) {
-                        if j.get("ping").is_some() {
-                            continue;
-                        }
-                    }
-                    self.boardcast(msg).await;
+                    info!("Recv: {:?}", alert);
+                    self.boardcast(&alert).await;
                 }
                 Err(_) => {
                     error!("Error receiving message");
@@ -83,3 +159,125 @@ impl TelegramBot {
     }
 }
 
+/// Send a rendered alert with its incident buttons, the message is edited on ack
+pub async fn send_alert(
+    bot: &Bot,
+    chat_id: String,
+    thread_id: Option<ThreadId>,
+    route: Option<usize>,
+    alert: &Alert,
+    rendered: &Rendered,
+) {
+    let markup = incident::get(alert.id).and_then(|incident| keyboard(&incident, route));
+    match send_rendered(bot, chat_id.clone(), thread_id, markup, rendered).await {
+        Ok(sent) => {
+            // the last chunk carries the buttons
+            let last_chunk = split_message(&rendered.text, rendered.format, CONFIG.telegram.max_message_len).pop();
+            if let (Some(msg), Some(text)) = (sent.last(), last_chunk) {
+                incident::attach_message(alert.id, SentMessage {
+                    chat_id: msg.chat.id,
+                    message_id: msg.id,
+                    text,
+                    format: rendered.format,
+                    route,
+                });
+            }
+        }
+        Err(e) => error!("[bot] send message to {} failed: {}", chat_id, e),
+    }
+}
+
+/// First route of the chat matching the alert
+pub fn route_for(chat: &ChatItem, alert: &Alert) -> Option<&'static TelegramRoute> {
+    route_index(chat, alert).map(|i| &CONFIG.telegram.route[i])
+}
+
+pub fn route_index(chat: &ChatItem, alert: &Alert) -> Option<usize> {
+    CONFIG
+        .telegram
+        .route
+        .iter()
+        .position(|route| route.chat_id == chat.chat_id() && route.matcher.matches(alert))
+}
+
+/// Topic of the chat for an alert: first matching route, otherwise the chat default
+pub fn route_thread(chat: &ChatItem, alert: &Alert) -> Option<ThreadId> {
+    route_for(chat, alert)
+        .and_then(|route| route.thread_id)
+        .or(chat.thread_id())
+        .map(|id| ThreadId(MessageId(id)))
+}
+
+/// Current on-call person of the schedule
+pub fn oncall_user(schedule: &str) -> Option<String> {
+    let schedule = oncall::schedule(schedule)?;
+    oncall::on_call(schedule, Utc::now()).map(|shift| shift.user)
+}
+
+/// Alert sent directly to the on-call person of the schedule
+pub async fn send_oncall_dm(bot: &Bot, schedule: &str, alert: &Alert, rendered: &Rendered) {
+    let Some(user) = oncall_user(schedule).and_then(|name| oncall::user(&name)) else {
+        warn!("[bot] nobody on call for schedule {}", schedule);
+        return;
+    };
+    send_alert(bot, user.id.to_string(), None, None, alert, rendered).await;
+}
+
+/// Send a rendered message, split into several messages when it is too long.
+/// The reply markup goes to the last message.
+/// Falls back to plain text when telegram rejects the markup.
+pub async fn send_rendered(
+    bot: &Bot,
+    chat_id: impl Into<Recipient>,
+    thread_id: Option<ThreadId>,
+    markup: Option<InlineKeyboardMarkup>,
+    message: &Rendered,
+) -> ResponseResult<Vec<Message>> {
+    let chat_id = chat_id.into();
+    let chunks = split_message(&message.text, message.format, CONFIG.telegram.max_message_len);
+    let count = chunks.len();
+    let mut sent = vec![];
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let markup = if i + 1 == count { markup.clone().map(ReplyMarkup::InlineKeyboard) } else { None };
+        let mut req = bot.send_message(chat_id.clone(), &chunk);
+        req.message_thread_id = thread_id;
+        req.reply_markup = markup.clone();
+        let ret = match parse_mode(message.format) {
+            Some(mode) => req.parse_mode(mode).await,
+            None => req.await,
+        };
+        let msg = match ret {
+            Err(RequestError::Api(ApiError::CantParseEntities(e))) => {
+                warn!("[bot] can't parse entities, resend as plain text: {}", e);
+                let mut req = bot.send_message(chat_id.clone(), chunk);
+                req.message_thread_id = thread_id;
+                req.reply_markup = markup;
+                req.await?
+            }
+            ret => ret?,
+        };
+        sent.push(msg);
+    }
+    Ok(sent)
+}
+
+pub async fn send_document(
+    bot: &Bot,
+    chat_id: impl Into<Recipient>,
+    thread_id: Option<ThreadId>,
+    name: &str,
+    content: String,
+) -> ResponseResult<Message> {
+    let file = InputFile::memory(content.into_bytes()).file_name(name.to_string());
+    let mut req = bot.send_document(chat_id, file);
+    req.message_thread_id = thread_id;
+    req.await
+}
+
+pub fn parse_mode(format: TemplateFormat) -> Option<ParseMode> {
+    match format {
+        TemplateFormat::Text => None,
+        TemplateFormat::Markdown => Some(ParseMode::MarkdownV2),
+        TemplateFormat::Html => Some(ParseMode::Html),
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::net::ToSocketAddrs;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use x509_parser::pem::Pem;
+use x509_parser::prelude::X509Certificate;
+
+use crate::alert::{Alert, Severity};
+use crate::boardcast::BROADCAST_SENDER;
+use crate::config::CONFIG;
+use crate::G_TOKIO_RUNTIME;
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Certificates of an endpoint or file, or why they could not be read
+pub type CertResult = Result<Vec<CertInfo>, String>;
+
+pub static STATUS: Lazy<Mutex<BTreeMap<String, CertResult>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+pub fn validate() -> anyhow::Result<()> {
+    if CONFIG.certs.days.is_empty() {
+        bail!("[certs] days must not be empty");
+    }
+    if CONFIG.certs.failures == 0 {
+        bail!("[certs] failures must be at least 1");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct CertInfo {
+    pub subject: String,
+    pub not_after: DateTime<Utc>,
+}
+
+impl CertInfo {
+    fn from_x509(cert: &X509Certificate) -> Self {
+        CertInfo {
+            subject: cert.subject().to_string(),
+            not_after: DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0).unwrap_or_default(),
+        }
+    }
+
+    pub fn days_left(&self) -> i64 {
+        (self.not_after - Utc::now()).num_days()
+    }
+}
+
+/// Certificate chain presented by a tls endpoint, leaf first
+async fn fetch_endpoint(endpoint: &str) -> Result<Vec<CertInfo>, String> {
+    let endpoint = endpoint.to_string();
+    tokio::task::spawn_blocking(move || fetch_chain(&endpoint))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()))
+}
+
+/// native-tls only exposes the leaf, openssl hands out the whole peer chain
+fn fetch_chain(endpoint: &str) -> Result<Vec<CertInfo>, String> {
+    let host = endpoint.rsplit_once(':').map_or(endpoint, |(host, _)| host);
+    let addr = endpoint
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("no address found")?;
+    let stream = std::net::TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+    // invalid and expired certificates still have to be inspected
+    let mut builder = SslConnector::builder(SslMethod::tls()).map_err(|e| e.to_string())?;
+    builder.set_verify(SslVerifyMode::NONE);
+    let tls = builder
+        .build()
+        .configure()
+        .map_err(|e| e.to_string())?
+        .verify_hostname(false)
+        .connect(host, stream)
+        .map_err(|e| e.to_string())?;
+    let chain = tls.ssl().peer_cert_chain().ok_or("no peer certificate")?;
+    let mut certs = vec![];
+    for cert in chain {
+        let der = cert.to_der().map_err(|e| e.to_string())?;
+        let (_, x509) = x509_parser::parse_x509_certificate(&der).map_err(|e| e.to_string())?;
+        certs.push(CertInfo::from_x509(&x509));
+    }
+    if certs.is_empty() {
+        return Err("no peer certificate".to_string());
+    }
+    Ok(certs)
+}
+
+async fn read_file(path: &std::path::Path) -> Result<Vec<CertInfo>, String> {
+    let data = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+    let mut certs = vec![];
+    for pem in Pem::iter_from_buffer(&data) {
+        let pem = pem.map_err(|e| e.to_string())?;
+        if pem.label != "CERTIFICATE" {
+            continue;
+        }
+        let x509 = pem.parse_x509().map_err(|e| e.to_string())?;
+        certs.push(CertInfo::from_x509(&x509));
+    }
+    if certs.is_empty() {
+        return Err("no certificate found".to_string());
+    }
+    Ok(certs)
+}
+
+/// Smallest threshold reached by `days_left`
+fn reached(days_left: i64) -> Option<i64> {
+    CONFIG.certs.days.iter().copied().filter(|d| days_left <= *d).min()
+}
+
+fn check(target: &str, certs: &[CertInfo], alerted: &mut Option<i64>) -> Option<Alert> {
+    let soonest = certs.iter().min_by_key(|c| c.not_after)?;
+    let days_left = soonest.days_left();
+    let title = format!("certificate of {} expires", target);
+    match reached(days_left) {
+        Some(threshold) if alerted.is_none_or(|a| threshold < a) => {
+            *alerted = Some(threshold);
+            let last = CONFIG.certs.days.iter().min().copied().unwrap_or_default();
+            let severity = if days_left < 0 || threshold == last { Severity::Critical } else { Severity::Warning };
+            let when = match days_left {
+                ..0 => "expired".to_string(),
+                0 => "expires today".to_string(),
+                days => format!("expires in {} days", days),
+            };
+            let body = format!(
+                "{}\n{} on {}",
+                soonest.subject,
+                when,
+                soonest.not_after.format("%Y-%m-%d %H:%M UTC")
+            );
+            Some(
+                Alert::new("certs", body)
+                    .with_title(title)
+                    .with_severity(severity)
+                    .with_label("target", target),
+            )
+        }
+        None if alerted.is_some() => {
+            *alerted = None;
+            let body = format!("renewed, valid until {}", soonest.not_after.format("%Y-%m-%d"));
+            Some(Alert::recovery("certs", title, body).with_label("target", target))
+        }
+        _ => None,
+    }
+}
+
+/// Alert once a target could not be read `failures` times in a row, recover on the next read
+fn check_read(target: &str, error: Option<&str>, failures: &mut u32) -> Option<Alert> {
+    let title = format!("certificate of {} unreadable", target);
+    let Some(error) = error else {
+        let failed = std::mem::take(failures);
+        return (failed >= CONFIG.certs.failures).then(|| {
+            let body = format!("readable again after {} failed checks", failed);
+            Alert::recovery("certs", title, body).with_label("target", target)
+        });
+    };
+    *failures += 1;
+    (*failures == CONFIG.certs.failures).then(|| {
+        Alert::new("certs", format!("failed {} times in a row: {}", failures, error))
+            .with_title(title)
+            .with_severity(Severity::Warning)
+            .with_label("target", target)
+    })
+}
+
+pub fn run_certs() {
+    let targets = CONFIG.certs.endpoints.len() + CONFIG.certs.files.len();
+    if targets == 0 {
+        return;
+    }
+    info!("[certs] checking {} certificates", targets);
+    G_TOKIO_RUNTIME.spawn(async {
+        let mut alerted: BTreeMap<String, Option<i64>> = BTreeMap::new();
+        let mut failures: BTreeMap<String, u32> = BTreeMap::new();
+        let mut ticker = tokio::time::interval(Duration::from_secs(CONFIG.certs.interval_secs.max(60)));
+        loop {
+            ticker.tick().await;
+            let mut results = vec![];
+            for endpoint in &CONFIG.certs.endpoints {
+                results.push((endpoint.clone(), fetch_endpoint(endpoint).await));
+            }
+            for file in &CONFIG.certs.files {
+                results.push((file.display().to_string(), read_file(file).await));
+            }
+            for (target, result) in results {
+                let failures = failures.entry(target.clone()).or_default();
+                let mut alerts: Vec<Alert> = check_read(&target, result.as_ref().err().map(String::as_str), failures)
+                    .into_iter()
+                    .collect();
+                match &result {
+                    Ok(certs) => alerts.extend(check(&target, certs, alerted.entry(target.clone()).or_default())),
+                    Err(e) => warn!("[certs] failed to check {}: {}", target, e),
+                }
+                for alert in alerts {
+                    if let Err(e) = BROADCAST_SENDER.get().unwrap().send(alert).await {
+                        error!("[certs] failed to send alert: {}", e);
+                    }
+                }
+                STATUS.lock().unwrap().insert(target, result);
+            }
+        }
+    });
+}
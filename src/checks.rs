@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use chrono::{DateTime, Local};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Method;
+use tokio::net::TcpStream;
+
+use crate::alert::Alert;
+use crate::boardcast::BROADCAST_SENDER;
+use crate::config::{Check, CONFIG};
+use crate::util::format_duration;
+use crate::G_TOKIO_RUNTIME;
+
+/// Last result of every check by name, shown by `/checks`
+pub static STATUS: Lazy<Mutex<BTreeMap<String, CheckStatus>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+#[derive(Debug, Clone)]
+pub struct CheckStatus {
+    pub ok: bool,
+    pub latency: Duration,
+    pub error: Option<String>,
+    pub consecutive_failures: u32,
+    /// alerting until the check succeeds again
+    pub firing: bool,
+    pub checked_at: DateTime<Local>,
+    /// when the check last changed between up and down
+    pub since: DateTime<Local>,
+}
+
+pub fn validate() -> anyhow::Result<()> {
+    for check in &CONFIG.check {
+        match (&check.url, &check.tcp) {
+            (Some(_), None) | (None, Some(_)) => {}
+            _ => bail!("[check] `{}` needs exactly one of url or tcp", check.name),
+        }
+        if let Some(re) = &check.body_regex {
+            Regex::new(re).map_err(|e| anyhow::anyhow!("[check] `{}`: {}", check.name, e))?;
+        }
+        Method::from_bytes(check.method.as_bytes())
+            .map_err(|_| anyhow::anyhow!("[check] `{}`: invalid method {}", check.name, check.method))?;
+    }
+    Ok(())
+}
+
+pub fn target(check: &Check) -> &str {
+    check.url.as_deref().or(check.tcp.as_deref()).unwrap_or_default()
+}
+
+async fn probe_tcp(addr: &str, timeout: Duration) -> Result<(), String> {
+    match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("connect failed: {}", e)),
+        Err(_) => Err(format!("connect timed out after {}s", timeout.as_secs())),
+    }
+}
+
+async fn probe_http(check: &Check, client: &reqwest::Client, url: &str, body_re: Option<&Regex>) -> Result<(), String> {
+    let method = Method::from_bytes(check.method.as_bytes()).unwrap_or(Method::GET);
+    let resp = client.request(method, url).send().await.map_err(|e| {
+        if e.is_timeout() {
+            format!("timed out after {}s", check.timeout_secs)
+        } else {
+            format!("request failed: {}", e)
+        }
+    })?;
+    let status = resp.status();
+    let accepted = match check.expect_status.is_empty() {
+        true => status.is_success() || status.is_redirection(),
+        false => check.expect_status.contains(&status.as_u16()),
+    };
+    if !accepted {
+        return Err(format!("unexpected status {}", status));
+    }
+    if let Some(re) = body_re {
+        let body = resp.text().await.map_err(|e| format!("failed to read body: {}", e))?;
+        if !re.is_match(&body) {
+            return Err(format!("body does not match `{}`", re.as_str()));
+        }
+    }
+    Ok(())
+}
+
+/// Record a result, returning the alert to send when it goes down or recovers
+fn record(check: &Check, result: Result<(), String>, latency: Duration) -> Option<Alert> {
+    let now = Local::now();
+    let mut status = STATUS.lock().unwrap();
+    let prev = status.get(&check.name).cloned();
+    let result_ok = result.is_ok();
+    let mut current = CheckStatus {
+        ok: result_ok,
+        latency,
+        error: result.err(),
+        consecutive_failures: 0,
+        firing: prev.as_ref().is_some_and(|p| p.firing),
+        checked_at: now,
+        since: match &prev {
+            Some(p) if p.ok == result_ok => p.since,
+            _ => now,
+        },
+    };
+    if !current.ok {
+        current.consecutive_failures = prev.as_ref().map_or(0, |p| p.consecutive_failures) + 1;
+    }
+
+    let title = format!("{} is down", check.name);
+    let alert = if !current.ok && !current.firing && current.consecutive_failures >= check.failures {
+        current.firing = true;
+        let body = format!(
+            "{} failed {} times in a row: {}",
+            target(check),
+            current.consecutive_failures,
+            current.error.as_deref().unwrap_or_default()
+        );
+        Some(
+            Alert::new("check", body)
+                .with_title(title)
+                .with_severity(check.severity)
+                .with_label("check", &check.name),
+        )
+    } else if current.ok && current.firing {
+        current.firing = false;
+        let down_for = prev.map(|p| now - p.since).unwrap_or_default();
+        let body = format!(
+            "{} is up again after {}, {} ms",
+            target(check),
+            format_duration(down_for),
+            latency.as_millis()
+        );
+        Some(Alert::recovery("check", title, body).with_label("check", &check.name))
+    } else {
+        None
+    };
+    status.insert(check.name.clone(), current);
+    alert
+}
+
+async fn run_check(check: &'static Check) {
+    let timeout = Duration::from_secs(check.timeout_secs);
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .danger_accept_invalid_certs(!check.verify_tls)
+        .build()
+        .unwrap_or_default();
+    let body_re = check.body_regex.as_ref().and_then(|re| Regex::new(re).ok());
+    let mut ticker = tokio::time::interval(Duration::from_secs(check.interval_secs.max(1)));
+    loop {
+        ticker.tick().await;
+        let start = Instant::now();
+        let mut result = match (&check.url, &check.tcp) {
+            (Some(url), _) => probe_http(check, &client, url, body_re.as_ref()).await,
+            (_, Some(addr)) => probe_tcp(addr, timeout).await,
+            _ => Err("nothing to check".to_string()),
+        };
+        let latency = start.elapsed();
+        if result.is_ok()
+            && let Some(max) = check.max_latency_ms
+            && latency.as_millis() as u64 > max
+        {
+            result = Err(format!("latency {} ms above {} ms", latency.as_millis(), max));
+        }
+        if let Err(e) = &result {
+            warn!("[check] {} failed: {}", check.name, e);
+        }
+        if let Some(alert) = record(check, result, latency)
+            && let Err(e) = BROADCAST_SENDER.get().unwrap().send(alert).await
+        {
+            error!("[check] failed to send alert: {}", e);
+        }
+    }
+}
+
+pub fn run_checks() {
+    for check in &CONFIG.check {
+        info!("[check] {} every {}s: {}", check.name, check.interval_secs, target(check));
+        G_TOKIO_RUNTIME.spawn(run_check(check));
+    }
+}
@@ -1,8 +1,11 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use once_cell::sync::{Lazy, OnceCell};
 use serde::{Deserialize, Serialize};
 
+use crate::alert::{Alert, Severity};
+
 pub static CONFIG_PATH: OnceCell<PathBuf> = OnceCell::new();
 
 pub static CONFIG: Lazy<BotteConfig> = Lazy::new(|| {
@@ -18,6 +21,506 @@ pub struct BotteConfig {
     pub mail: Option<Mail>,
     pub webhook: Option<WebHook>,
     pub telegram: TelegramCfg,
+    #[serde(default)]
+    pub template: Vec<TemplateCfg>,
+    pub smtp: Option<Smtp>,
+    #[serde(default)]
+    pub escalation: Vec<Escalation>,
+    #[serde(default)]
+    pub oncall: Vec<Oncall>,
+    #[serde(default)]
+    pub shell: ShellCfg,
+    #[serde(default)]
+    pub runbook: Vec<Runbook>,
+    /// alias -> log file readable with `/tail` and `/grep`
+    #[serde(default)]
+    pub logs: BTreeMap<String, PathBuf>,
+    #[serde(default)]
+    pub systemd: SystemdCfg,
+    #[serde(default)]
+    pub docker: DockerCfg,
+    #[serde(default)]
+    pub monitor: MonitorCfg,
+    #[serde(default)]
+    pub watch: WatchCfg,
+    #[serde(default)]
+    pub logtail: Vec<LogTail>,
+    #[serde(default)]
+    pub check: Vec<Check>,
+    #[serde(default)]
+    pub certs: CertsCfg,
+    #[serde(default)]
+    pub heartbeat: Vec<Heartbeat>,
+    #[serde(default)]
+    pub probe: Vec<Probe>,
+    #[serde(default)]
+    pub report: Vec<Report>,
+    #[serde(default)]
+    pub history: HistoryCfg,
+}
+
+/// Host metrics recorded for `/graph`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HistoryCfg {
+    /// off by default, samples are written to `path`
+    #[serde(default)]
+    pub enable: bool,
+    #[serde(default = "default_history_interval")]
+    pub interval_secs: u64,
+    /// how long samples are kept, e.g. `7d`
+    #[serde(default = "default_history_retention")]
+    pub retention: String,
+    /// json lines file the samples are appended to
+    #[serde(default = "default_history_path")]
+    pub path: PathBuf,
+    /// ttf/otf font for chart labels, common system fonts are tried otherwise
+    #[serde(default)]
+    pub font: Option<PathBuf>,
+}
+
+impl Default for HistoryCfg {
+    fn default() -> Self {
+        HistoryCfg {
+            enable: false,
+            interval_secs: default_history_interval(),
+            retention: default_history_retention(),
+            path: default_history_path(),
+            font: None,
+        }
+    }
+}
+
+fn default_history_interval() -> u64 {
+    60
+}
+
+fn default_history_retention() -> String {
+    "7d".to_string()
+}
+
+fn default_history_path() -> PathBuf {
+    PathBuf::from("data/metrics.jsonl")
+}
+
+/// A health summary posted on a cron schedule
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Report {
+    pub name: String,
+    /// cron expression, e.g. `0 9 * * *`
+    pub schedule: String,
+    /// defaults to every chat of `allow_chat_id`
+    #[serde(default)]
+    pub chats: Vec<ChatItem>,
+    /// uptime | metrics | top | alerts | checks | heartbeats, all by default
+    #[serde(default = "default_report_sections")]
+    pub sections: Vec<String>,
+}
+
+fn default_report_sections() -> Vec<String> {
+    ["uptime", "metrics", "top", "alerts", "checks", "heartbeats"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// A shell command run on a cron schedule, alerting on its exit code or output
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Probe {
+    pub name: String,
+    pub command: String,
+    /// cron expression, e.g. `*/5 * * * *`
+    pub schedule: String,
+    #[serde(default = "default_shell_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub expect_exit: i32,
+    /// alert when the output does not match
+    #[serde(default)]
+    pub expect_regex: Option<String>,
+    /// alert when the output matches
+    #[serde(default)]
+    pub alert_regex: Option<String>,
+    /// first capture group is the value, defaults to the first number of the output
+    #[serde(default)]
+    pub value_regex: Option<String>,
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+/// Pinged on `/heartbeat/{name}`, alerts when no ping came within `interval` + `grace`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Heartbeat {
+    pub name: String,
+    /// expected time between pings, e.g. `1d`
+    pub interval: String,
+    #[serde(default = "default_grace")]
+    pub grace: String,
+    #[serde(default = "default_watch_severity")]
+    pub severity: Severity,
+}
+
+fn default_grace() -> String {
+    "5m".to_string()
+}
+
+/// Certificates checked for their expiry date
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CertsCfg {
+    /// `host:port`, every certificate of the presented chain is checked
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    /// PEM files, every certificate in them is checked
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    /// alert when this many days or less are left, the smallest is critical
+    #[serde(default = "default_cert_days")]
+    pub days: Vec<i64>,
+    #[serde(default = "default_cert_interval")]
+    pub interval_secs: u64,
+    /// consecutive failed reads of a target before alerting
+    #[serde(default = "default_cert_failures")]
+    pub failures: u32,
+}
+
+impl Default for CertsCfg {
+    fn default() -> Self {
+        CertsCfg {
+            endpoints: vec![],
+            files: vec![],
+            days: default_cert_days(),
+            interval_secs: default_cert_interval(),
+            failures: default_cert_failures(),
+        }
+    }
+}
+
+fn default_cert_days() -> Vec<i64> {
+    vec![30, 14, 7, 1]
+}
+
+fn default_cert_interval() -> u64 {
+    6 * 3600
+}
+
+fn default_cert_failures() -> u32 {
+    3
+}
+
+/// Uptime check of an http(s) url or a tcp `host:port`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Check {
+    pub name: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub tcp: Option<String>,
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// accepted status codes, any 2xx or 3xx when empty
+    #[serde(default)]
+    pub expect_status: Vec<u16>,
+    /// regex the response body must match
+    #[serde(default)]
+    pub body_regex: Option<String>,
+    #[serde(default)]
+    pub max_latency_ms: Option<u64>,
+    #[serde(default = "default_true")]
+    pub verify_tls: bool,
+    #[serde(default = "default_check_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_check_timeout")]
+    pub timeout_secs: u64,
+    /// consecutive failures before alerting
+    #[serde(default = "default_check_failures")]
+    pub failures: u32,
+    #[serde(default = "default_watch_severity")]
+    pub severity: Severity,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_check_interval() -> u64 {
+    60
+}
+
+fn default_check_timeout() -> u64 {
+    10
+}
+
+fn default_check_failures() -> u32 {
+    3
+}
+
+/// A log file followed for lines matching its rules
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LogTail {
+    pub path: PathBuf,
+    /// read the existing content instead of only new lines
+    #[serde(default)]
+    pub from_start: bool,
+    #[serde(default = "default_poll_ms")]
+    pub poll_ms: u64,
+    #[serde(default)]
+    pub rule: Vec<LogRule>,
+}
+
+fn default_poll_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LogRule {
+    pub name: String,
+    pub regex: String,
+    #[serde(default)]
+    pub severity: Severity,
+    /// may use captures as `$1` or `${name}`, defaults to the rule name
+    #[serde(default)]
+    pub title: Option<String>,
+    /// defaults to the whole line
+    #[serde(default)]
+    pub body: Option<String>,
+    /// at most one alert per window, e.g. `5m`
+    #[serde(default)]
+    pub rate_limit: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WatchCfg {
+    #[serde(default = "default_watch_interval")]
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub process: Vec<WatchProcess>,
+}
+
+impl Default for WatchCfg {
+    fn default() -> Self {
+        WatchCfg {
+            interval_secs: default_watch_interval(),
+            process: vec![],
+        }
+    }
+}
+
+fn default_watch_interval() -> u64 {
+    15
+}
+
+/// An expected process, found by name, command line or pidfile
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WatchProcess {
+    pub name: String,
+    /// substring of the process name
+    #[serde(default)]
+    pub process: Option<String>,
+    /// regex matched against the full command line
+    #[serde(default)]
+    pub cmdline: Option<String>,
+    #[serde(default)]
+    pub pidfile: Option<PathBuf>,
+    #[serde(default = "default_instances")]
+    pub instances: usize,
+    #[serde(default)]
+    pub max_rss_mb: Option<f64>,
+    #[serde(default)]
+    pub max_cpu: Option<f64>,
+    #[serde(default = "default_watch_severity")]
+    pub severity: Severity,
+}
+
+fn default_instances() -> usize {
+    1
+}
+
+fn default_watch_severity() -> Severity {
+    Severity::Critical
+}
+
+/// Host resources sampled in the background, alerting when a threshold is crossed
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MonitorCfg {
+    #[serde(default = "default_monitor_interval")]
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub threshold: Vec<Threshold>,
+}
+
+impl Default for MonitorCfg {
+    fn default() -> Self {
+        MonitorCfg {
+            interval_secs: default_monitor_interval(),
+            threshold: vec![],
+        }
+    }
+}
+
+fn default_monitor_interval() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Threshold {
+    /// cpu | mem | swap | disk | inode | load | net_rx | net_tx
+    pub metric: String,
+    /// mount point of a disk or inode threshold, the fullest mount otherwise
+    #[serde(default)]
+    pub mount: Option<String>,
+    pub above: f64,
+    /// recovered once below, defaults to 90% of `above`
+    #[serde(default)]
+    pub recover: Option<f64>,
+    /// how long `above` must hold before alerting, e.g. `5m`
+    #[serde(default = "default_threshold_for", rename = "for")]
+    pub duration: String,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+fn default_threshold_for() -> String {
+    "1m".to_string()
+}
+
+/// Containers which `/docker` may inspect and restart
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DockerCfg {
+    #[serde(default = "default_docker_socket")]
+    pub socket: PathBuf,
+    /// container names, a trailing `*` matches a prefix
+    #[serde(default)]
+    pub containers: Vec<String>,
+    #[serde(default = "default_docker_log_lines")]
+    pub log_lines: usize,
+}
+
+impl Default for DockerCfg {
+    fn default() -> Self {
+        DockerCfg {
+            socket: default_docker_socket(),
+            containers: vec![],
+            log_lines: default_docker_log_lines(),
+        }
+    }
+}
+
+fn default_docker_socket() -> PathBuf {
+    PathBuf::from("/var/run/docker.sock")
+}
+
+fn default_docker_log_lines() -> usize {
+    50
+}
+
+/// Units which `/svc` may inspect and restart
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SystemdCfg {
+    #[serde(default)]
+    pub units: Vec<String>,
+    /// journal lines shown by `/svc status`
+    #[serde(default = "default_journal_lines")]
+    pub journal_lines: usize,
+    #[serde(default = "default_shell_timeout")]
+    pub timeout_secs: u64,
+}
+
+impl Default for SystemdCfg {
+    fn default() -> Self {
+        SystemdCfg {
+            units: vec![],
+            journal_lines: default_journal_lines(),
+            timeout_secs: default_shell_timeout(),
+        }
+    }
+}
+
+fn default_journal_lines() -> usize {
+    10
+}
+
+/// A named action exposed as `/run <name> [params..]`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Runbook {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// program, executed without a shell
+    pub command: String,
+    /// may reference parameters as `{param}`
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default = "default_shell_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_runbook_role")]
+    pub role: Role,
+    /// ask for confirmation before running
+    #[serde(default)]
+    pub confirm: bool,
+    #[serde(default)]
+    pub param: Vec<RunbookParam>,
+}
+
+fn default_runbook_role() -> Role {
+    Role::Operator
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RunbookParam {
+    pub name: String,
+    /// regex the whole value must match
+    #[serde(default = "default_param_pattern")]
+    pub pattern: String,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// no leading `-`, values must not turn into options of the command
+fn default_param_pattern() -> String {
+    "^[A-Za-z0-9_.][A-Za-z0-9_.-]*$".to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ShellCfg {
+    #[serde(default = "default_shell_timeout")]
+    pub timeout_secs: u64,
+    /// regex, when not empty a command must match one of them and may not
+    /// contain shell operators (`;`, `&`, `|`, `` ` ``, `$`, `<`, `>`, newlines)
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// regex, commands matching any of them are refused
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// how often the telegram message is updated with new output
+    #[serde(default = "default_update_interval")]
+    pub update_interval_ms: u64,
+}
+
+impl Default for ShellCfg {
+    fn default() -> Self {
+        ShellCfg {
+            timeout_secs: default_shell_timeout(),
+            allow: vec![],
+            deny: vec![],
+            update_interval_ms: default_update_interval(),
+        }
+    }
+}
+
+fn default_shell_timeout() -> u64 {
+    30
+}
+
+fn default_update_interval() -> u64 {
+    1500
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -28,6 +531,68 @@ pub struct Mail {
     pub filter_users: Vec<String>,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Smtp {
+    /// host:port, implicit tls
+    pub smtp_service: String,
+    pub email: String,
+    pub passwd: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Escalation {
+    pub name: String,
+    /// only alerts of these severities are escalated
+    #[serde(default = "default_escalation_severity")]
+    pub severity: Vec<Severity>,
+    pub step: Vec<EscalationStep>,
+}
+
+fn default_escalation_severity() -> Vec<Severity> {
+    vec![Severity::Critical]
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EscalationStep {
+    /// minutes since the alert fired
+    pub after_min: u64,
+    /// `chat:<id>`, `chat:<id>/<thread_id>`, `hook:<url>`, `mail:<address>` or `oncall:<schedule>`
+    pub targets: Vec<String>,
+}
+
+/// Weekly (or every `rotation_days`) rotation over `users`, handing off at `start` time
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Oncall {
+    pub name: String,
+    /// e.g. `Asia/Shanghai`
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// first shift of `users[0]`, `%Y-%m-%d %H:%M` in the schedule time zone
+    pub start: String,
+    #[serde(default = "default_rotation_days")]
+    pub rotation_days: u32,
+    /// names from `[telegram.users]`
+    pub users: Vec<String>,
+    #[serde(default, rename = "override")]
+    pub overrides: Vec<OncallOverride>,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_rotation_days() -> u32 {
+    7
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OncallOverride {
+    pub user: String,
+    /// `%Y-%m-%d %H:%M` in the schedule time zone
+    pub start: String,
+    pub end: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub enum HookType {
     #[serde(rename = "dingtalk")]
@@ -56,5 +621,184 @@ pub struct WebHook {
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TelegramCfg {
-    pub allow_chat_id: Vec<String>
-}
\ No newline at end of file
+    pub allow_chat_id: Vec<ChatItem>,
+    /// chats allowed to run commands, defaults to `allow_chat_id`
+    #[serde(default)]
+    pub command_chat_id: Option<Vec<String>>,
+    /// reply to commands from other chats, silent when unset
+    #[serde(default)]
+    pub unknown_chat_reply: Option<String>,
+    /// commands (with optional sub command, e.g. `svc restart`) asking for confirmation
+    #[serde(default = "default_confirm_commands")]
+    pub confirm_commands: Vec<String>,
+    #[serde(default = "default_confirm_timeout")]
+    pub confirm_timeout_secs: u64,
+    /// messages longer than this are split into several sends
+    #[serde(default = "default_max_message_len")]
+    pub max_message_len: usize,
+    /// alert bodies longer than this are sent as a `.txt` document
+    #[serde(default)]
+    pub document_threshold: Option<usize>,
+    /// first matching route picks the topic of a chat
+    #[serde(default)]
+    pub route: Vec<TelegramRoute>,
+    /// people referenced by name in on-call schedules and roles
+    #[serde(default)]
+    pub users: BTreeMap<String, TelegramUser>,
+    /// without roles everybody is a viewer
+    #[serde(default)]
+    pub roles: Option<Roles>,
+    /// command name -> minimum role, overrides the defaults
+    #[serde(default)]
+    pub permissions: BTreeMap<String, Role>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Anyone,
+    Viewer,
+    Operator,
+    Admin,
+}
+
+/// A telegram user id, or a name from `[telegram.users]`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum UserRef {
+    Id(u64),
+    Name(String),
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct Roles {
+    #[serde(default)]
+    pub admins: Vec<UserRef>,
+    #[serde(default)]
+    pub operators: Vec<UserRef>,
+    #[serde(default)]
+    pub viewers: Vec<UserRef>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TelegramUser {
+    pub id: i64,
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+fn default_confirm_commands() -> Vec<String> {
+    vec!["exit".to_string(), "shell".to_string(), "svc restart".to_string(), "docker restart".to_string()]
+}
+
+fn default_confirm_timeout() -> u64 {
+    60
+}
+
+fn default_max_message_len() -> usize {
+    4096
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum ChatItem {
+    Simple(String),
+    /// a forum supergroup with the topic used by default
+    Detailed {
+        chat_id: String,
+        #[serde(default)]
+        thread_id: Option<i32>,
+    },
+}
+
+impl ChatItem {
+    pub fn chat_id(&self) -> &str {
+        match self {
+            ChatItem::Simple(chat_id) => chat_id,
+            ChatItem::Detailed { chat_id, .. } => chat_id,
+        }
+    }
+
+    pub fn thread_id(&self) -> Option<i32> {
+        match self {
+            ChatItem::Simple(_) => None,
+            ChatItem::Detailed { thread_id, .. } => *thread_id,
+        }
+    }
+}
+
+/// Conditions on an alert, every non-empty one must match
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct AlertMatch {
+    #[serde(default)]
+    pub source: Vec<String>,
+    #[serde(default)]
+    pub severity: Vec<Severity>,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl AlertMatch {
+    pub fn matches(&self, alert: &Alert) -> bool {
+        (self.source.is_empty() || self.source.contains(&alert.source))
+            && (self.severity.is_empty() || self.severity.contains(&alert.severity))
+            && self.labels.iter().all(|(k, v)| alert.labels.get(k) == Some(v))
+    }
+}
+
+/// Alerts matching the conditions go into a topic of a forum chat,
+/// and follow an escalation policy while not acknowledged
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TelegramRoute {
+    pub chat_id: String,
+    #[serde(default)]
+    pub thread_id: Option<i32>,
+    #[serde(default)]
+    pub escalation: Option<String>,
+    /// mention the on-call person of this schedule
+    #[serde(default)]
+    pub oncall: Option<String>,
+    /// also send the alert to the on-call person directly
+    #[serde(default)]
+    pub oncall_dm: bool,
+    /// runbook buttons under the alert
+    #[serde(default)]
+    pub action: Vec<RouteAction>,
+    #[serde(flatten)]
+    pub matcher: AlertMatch,
+}
+
+/// A button running a runbook, its params are taken from the alert labels
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RouteAction {
+    pub label: String,
+    pub runbook: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub enum TemplateFormat {
+    #[default]
+    #[serde(rename = "text")]
+    Text,
+    #[serde(rename = "markdown")]
+    Markdown,
+    #[serde(rename = "html")]
+    Html,
+}
+
+fn any() -> String {
+    "*".to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TemplateCfg {
+    /// telegram | dingtalk | webhook | *
+    #[serde(default = "any")]
+    pub sink: String,
+    /// mail | api | mock | ... | *
+    #[serde(default = "any")]
+    pub source: String,
+    #[serde(default)]
+    pub format: TemplateFormat,
+    pub text: String,
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use sysinfo::Disks;
+
+/// Kernel and virtual filesystems which are not worth reporting
+const PSEUDO_FS: [&str; 27] = [
+    "autofs", "binfmt_misc", "bpf", "cgroup", "cgroup2", "configfs", "debugfs", "devfs", "devpts", "devtmpfs",
+    "efivarfs", "fusectl", "fuse.lxcfs", "fuse.gvfsd-fuse", "fuse.portal", "hugetlbfs", "mqueue", "nsfs", "overlay",
+    "proc", "pstore", "ramfs", "rpc_pipefs", "securityfs", "squashfs", "sysfs", "tracefs",
+];
+
+#[derive(Debug, Clone)]
+pub struct Mount {
+    pub mount: String,
+    pub device: String,
+    pub fs: String,
+    pub total: u64,
+    /// used by files, reserved blocks excluded
+    pub used: u64,
+    /// free for unprivileged users
+    pub available: u64,
+    /// total and free inodes, none for filesystems without a fixed inode table
+    pub inodes: Option<(u64, u64)>,
+}
+
+impl Mount {
+    /// Same as `df`: reserved blocks count neither as used nor as available
+    pub fn percent(&self) -> f64 {
+        match self.used + self.available {
+            0 => 0.0,
+            size => self.used as f64 / size as f64 * 100.0,
+        }
+    }
+
+    pub fn inode_percent(&self) -> Option<f64> {
+        let (total, free) = self.inodes?;
+        Some(total.saturating_sub(free) as f64 / total as f64 * 100.0)
+    }
+}
+
+fn statvfs(path: &Path) -> Option<libc::statvfs> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    match unsafe { libc::statvfs(path.as_ptr(), &mut stat) } {
+        0 => Some(stat),
+        _ => None,
+    }
+}
+
+/// Real filesystems, a device mounted several times (bind mounts) is listed once
+/// under its shortest mount point
+// statvfs field widths differ between platforms
+#[allow(clippy::unnecessary_cast)]
+pub fn mounts() -> Vec<Mount> {
+    let mut by_device: BTreeMap<String, Mount> = BTreeMap::new();
+    for disk in Disks::new_with_refreshed_list().iter() {
+        let fs = disk.file_system().to_string_lossy().to_string();
+        if disk.total_space() == 0 || PSEUDO_FS.contains(&fs.as_str()) || fs == "tmpfs" {
+            continue;
+        }
+        let mut mount = Mount {
+            mount: disk.mount_point().to_string_lossy().to_string(),
+            device: disk.name().to_string_lossy().to_string(),
+            fs,
+            total: disk.total_space(),
+            used: disk.total_space() - disk.available_space(),
+            available: disk.available_space(),
+            inodes: None,
+        };
+        if let Some(stat) = statvfs(disk.mount_point()) {
+            let block = stat.f_frsize as u64;
+            mount.total = stat.f_blocks as u64 * block;
+            mount.used = (stat.f_blocks - stat.f_bfree) as u64 * block;
+            mount.available = stat.f_bavail as u64 * block;
+            mount.inodes = (stat.f_files > 0).then_some((stat.f_files as u64, stat.f_ffree as u64));
+        }
+        match by_device.get(&mount.device) {
+            Some(seen) if seen.mount.len() <= mount.mount.len() => {}
+            _ => {
+                by_device.insert(mount.device.clone(), mount);
+            }
+        }
+    }
+    let mut mounts: Vec<Mount> = by_device.into_values().collect();
+    mounts.sort_by(|a, b| a.mount.cmp(&b.mount));
+    mounts
+}
+
+/// Mount point holding `path`, e.g. `/data/db` is on `/data` or `/`
+pub fn find<'a>(mounts: &'a [Mount], path: &str) -> Option<&'a Mount> {
+    mounts
+        .iter()
+        .filter(|m| Path::new(path).starts_with(&m.mount))
+        .max_by_key(|m| m.mount.len())
+}
+
+pub fn human(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{}B", bytes),
+        _ => format!("{:.1}{}", value, UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(path: &str, used: u64, available: u64, inodes: Option<(u64, u64)>) -> Mount {
+        Mount {
+            mount: path.to_string(),
+            device: path.to_string(),
+            fs: "ext4".to_string(),
+            total: used + available + 10,
+            used,
+            available,
+            inodes,
+        }
+    }
+
+    #[test]
+    fn usage_like_df() {
+        let m = mount("/", 30, 70, Some((200, 50)));
+        assert_eq!(m.percent(), 30.0);
+        assert_eq!(m.inode_percent(), Some(75.0));
+        assert_eq!(mount("/", 0, 0, None).percent(), 0.0);
+        assert_eq!(mount("/", 0, 0, None).inode_percent(), None);
+    }
+
+    #[test]
+    fn longest_mount_prefix() {
+        let mounts = [mount("/", 1, 1, None), mount("/data", 1, 1, None)];
+        assert_eq!(find(&mounts, "/data/db").unwrap().mount, "/data");
+        assert_eq!(find(&mounts, "/database").unwrap().mount, "/");
+        assert_eq!(find(&mounts, "/var").unwrap().mount, "/");
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(human(512), "512B");
+        assert_eq!(human(1536), "1.5K");
+        assert_eq!(human(3 * 1024 * 1024 * 1024), "3.0G");
+    }
+}
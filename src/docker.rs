@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+use crate::config::CONFIG;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Container {
+    pub id: String,
+    pub names: Vec<String>,
+    pub image: String,
+    /// running, exited, restarting ...
+    pub state: String,
+    /// `Up 3 hours (healthy)`
+    pub status: String,
+}
+
+impl Container {
+    pub fn name(&self) -> &str {
+        self.names.first().map(|n| n.trim_start_matches('/')).unwrap_or(&self.id)
+    }
+
+    pub fn emoji(&self) -> &'static str {
+        match self.state.as_str() {
+            "running" if self.status.contains("unhealthy") => "🟡",
+            "running" => "🟢",
+            "restarting" | "paused" => "🟡",
+            "exited" | "dead" => "🔴",
+            _ => "⚪",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Inspect {
+    pub restart_count: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub cpu_percent: f64,
+    pub memory: u64,
+    pub memory_limit: u64,
+}
+
+/// Container names as docker accepts them: `[A-Za-z0-9][A-Za-z0-9_.-]*`
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+/// Whether the container name is valid and in the allowlist
+pub fn allowed(name: &str) -> bool {
+    if !valid_name(name) {
+        return false;
+    }
+    CONFIG.docker.containers.iter().any(|pattern| match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    })
+}
+
+/// HTTP/1.0 over the engine socket, so the body is neither chunked nor kept alive
+async fn request(method: &str, path: &str) -> Result<(u16, Vec<u8>), String> {
+    let exchange = async {
+        let mut stream = UnixStream::connect(&CONFIG.docker.socket).await?;
+        let head = format!(
+            "{} {} HTTP/1.0\r\nHost: docker\r\nContent-Length: 0\r\n\r\n",
+            method, path
+        );
+        stream.write_all(head.as_bytes()).await?;
+        let mut response = vec![];
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    let response = tokio::time::timeout(TIMEOUT, exchange)
+        .await
+        .map_err(|_| "docker engine timed out".to_string())?
+        .map_err(|e| format!("docker engine at {}: {}", CONFIG.docker.socket.display(), e))?;
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or("invalid response from docker engine")?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or("invalid response from docker engine")?;
+    Ok((status, response[split + 4..].to_vec()))
+}
+
+async fn get<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let (status, body) = request("GET", path).await?;
+    if status != 200 {
+        return Err(error_message(status, &body));
+    }
+    serde_json::from_slice(&body).map_err(|e| format!("invalid response from docker engine: {}", e))
+}
+
+fn error_message(status: u16, body: &[u8]) -> String {
+    #[derive(Deserialize)]
+    struct Error {
+        message: String,
+    }
+    match serde_json::from_slice::<Error>(body) {
+        Ok(e) => e.message,
+        Err(_) => format!("docker engine returned {}", status),
+    }
+}
+
+/// Allowlisted containers, stopped ones included
+pub async fn list() -> Result<Vec<Container>, String> {
+    let containers: Vec<Container> = get("/containers/json?all=true").await?;
+    Ok(containers.into_iter().filter(|c| allowed(c.name())).collect())
+}
+
+/// Percent-encoded path segment, the name never reaches the path or query unescaped
+fn segment(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'.' | b'-' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub async fn inspect(name: &str) -> Result<Inspect, String> {
+    get(&format!("/containers/{}/json", segment(name))).await
+}
+
+pub async fn restart(name: &str) -> Result<(), String> {
+    let (status, body) = request("POST", &format!("/containers/{}/restart?t=10", segment(name))).await?;
+    match status {
+        204 => Ok(()),
+        _ => Err(error_message(status, &body)),
+    }
+}
+
+/// Last lines of stdout and stderr
+pub async fn logs(name: &str, lines: usize) -> Result<String, String> {
+    let path = format!("/containers/{}/logs?stdout=true&stderr=true&tail={}", segment(name), lines);
+    let (status, body) = request("GET", &path).await?;
+    if status != 200 {
+        return Err(error_message(status, &body));
+    }
+    Ok(demux(&body))
+}
+
+/// Containers without a tty multiplex stdout and stderr in frames
+/// of an 8 bytes header (stream, 0, 0, 0, big endian size) and the payload
+fn demux(body: &[u8]) -> String {
+    let framed = body.len() >= 8 && body[0] <= 2 && body[1..4] == [0, 0, 0];
+    if !framed {
+        return String::from_utf8_lossy(body).to_string();
+    }
+    let mut out = vec![];
+    let mut rest = body;
+    while rest.len() >= 8 {
+        let size = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let end = (8 + size).min(rest.len());
+        out.extend_from_slice(&rest[8..end]);
+        rest = &rest[end..];
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// CPU and memory usage, the engine samples for about a second
+pub async fn stats(name: &str) -> Result<Stats, String> {
+    let v: serde_json::Value = get(&format!("/containers/{}/stats?stream=false", segment(name))).await?;
+    let u = |pointer: &str| v.pointer(pointer).and_then(|x| x.as_u64()).unwrap_or(0);
+    let cpu_delta = u("/cpu_stats/cpu_usage/total_usage").saturating_sub(u("/precpu_stats/cpu_usage/total_usage"));
+    let system_delta = u("/cpu_stats/system_cpu_usage").saturating_sub(u("/precpu_stats/system_cpu_usage"));
+    let cpus = match u("/cpu_stats/online_cpus") {
+        0 => v
+            .pointer("/cpu_stats/cpu_usage/percpu_usage")
+            .and_then(|x| x.as_array())
+            .map_or(1, |a| a.len() as u64),
+        n => n,
+    };
+    let cpu_percent = if system_delta > 0 {
+        cpu_delta as f64 / system_delta as f64 * cpus as f64 * 100.0
+    } else {
+        0.0
+    };
+    // page cache is reclaimable, `docker stats` leaves it out as well
+    let cache = match u("/memory_stats/stats/inactive_file") {
+        0 => u("/memory_stats/stats/total_inactive_file"),
+        n => n,
+    };
+    Ok(Stats {
+        cpu_percent,
+        memory: u("/memory_stats/usage").saturating_sub(cache),
+        memory_limit: u("/memory_stats/limit"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert!(valid_name("app-web_1.blue"));
+        assert!(!valid_name(""));
+        assert!(!valid_name("-app"));
+        assert!(!valid_name("app-x/../../db"));
+        assert!(!valid_name("app-x?signal=KILL"));
+        assert!(!valid_name("app x"));
+    }
+
+    #[test]
+    fn segments_are_encoded() {
+        assert_eq!(segment("app-1.web"), "app-1.web");
+        assert_eq!(segment("a/../b?t=1"), "a%2F..%2Fb%3Ft%3D1");
+    }
+
+    #[test]
+    fn demux_frames() {
+        let mut body = vec![1, 0, 0, 0, 0, 0, 0, 6];
+        body.extend_from_slice(b"hello\n");
+        body.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 4]);
+        body.extend_from_slice(b"err\n");
+        assert_eq!(demux(&body), "hello\nerr\n");
+        assert_eq!(demux(b"plain tty output\n"), "plain tty output\n");
+    }
+}
@@ -0,0 +1,254 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use chrono::Local;
+use log::{error, info, warn};
+use teloxide::types::{MessageId, ThreadId};
+use teloxide::utils::{html, markdown};
+
+use crate::alert::Alert;
+use crate::bot::BOT;
+use crate::bot::telegram::{send_alert, send_oncall_dm};
+use crate::config::{Escalation, TemplateFormat, CONFIG};
+use crate::incident::{IncidentStatus, INCIDENTS};
+use crate::mail::smtp::send_mail;
+use crate::oncall;
+use crate::template::{self, escape, Rendered};
+use crate::webhook::post;
+use crate::G_TOKIO_RUNTIME;
+
+const TICK: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Chat { chat_id: String, thread_id: Option<i32> },
+    Hook(String),
+    Mail(String),
+    /// direct message to whoever is on call
+    Oncall(String),
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (kind, value) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("invalid target `{}`, expect `<kind>:<value>`", s))?;
+        match kind {
+            "chat" => {
+                let (chat_id, thread_id) = match value.split_once('/') {
+                    Some((chat_id, thread_id)) => (chat_id, Some(thread_id.parse::<i32>()?)),
+                    None => (value, None),
+                };
+                Ok(Target::Chat {
+                    chat_id: chat_id.to_string(),
+                    thread_id,
+                })
+            }
+            "hook" => Ok(Target::Hook(value.to_string())),
+            "mail" => Ok(Target::Mail(value.to_string())),
+            "oncall" => match oncall::schedule(value) {
+                Some(_) => Ok(Target::Oncall(value.to_string())),
+                None => bail!("unknown oncall schedule `{}`", value),
+            },
+            _ => bail!("unknown target kind `{}` in `{}`, expect chat, hook, mail or oncall", kind, s),
+        }
+    }
+}
+
+/// Chat ids of the `chat:` targets
+pub fn chat_targets() -> impl Iterator<Item = String> {
+    CONFIG
+        .escalation
+        .iter()
+        .flat_map(|policy| &policy.step)
+        .flat_map(|step| &step.targets)
+        .filter_map(|target| match target.parse::<Target>() {
+            Ok(Target::Chat { chat_id, .. }) => Some(chat_id),
+            _ => None,
+        })
+}
+
+/// Check that routes point to existing policies and every target parses
+pub fn validate() -> anyhow::Result<()> {
+    for policy in &CONFIG.escalation {
+        for step in &policy.step {
+            for target in &step.targets {
+                target
+                    .parse::<Target>()
+                    .map_err(|e| anyhow!("escalation `{}`: {}", policy.name, e))?;
+            }
+        }
+    }
+    for route in &CONFIG.telegram.route {
+        if let Some(name) = &route.escalation
+            && !CONFIG.escalation.iter().any(|p| &p.name == name)
+        {
+            bail!("route of chat {} uses unknown escalation `{}`", route.chat_id, name);
+        }
+    }
+    Ok(())
+}
+
+pub fn run_escalation() {
+    if CONFIG.escalation.is_empty() {
+        return;
+    }
+    info!("[escalation] {} escalation policies enabled", CONFIG.escalation.len());
+    G_TOKIO_RUNTIME.spawn(async {
+        loop {
+            tokio::time::sleep(TICK).await;
+            tick().await;
+        }
+    });
+}
+
+/// Policy of the first matching route whose policy covers the alert severity
+fn policy(alert: &Alert) -> Option<&'static Escalation> {
+    CONFIG
+        .telegram
+        .route
+        .iter()
+        .filter(|route| route.matcher.matches(alert))
+        .filter_map(|route| route.escalation.as_ref())
+        .find_map(|name| {
+            CONFIG
+                .escalation
+                .iter()
+                .find(|p| &p.name == name && p.severity.contains(&alert.severity))
+        })
+}
+
+async fn tick() {
+    let now = Local::now();
+    // collect due steps under the lock, notify after releasing it
+    let mut due = vec![];
+    {
+        let mut incidents = INCIDENTS.lock().unwrap();
+        for incident in incidents.values_mut() {
+            if incident.status != IncidentStatus::Firing
+                || incident.silenced_until.is_some_and(|until| until > now)
+            {
+                continue;
+            }
+            let Some(policy) = policy(&incident.alert) else {
+                continue;
+            };
+            let Some(step) = policy.step.get(incident.escalation_level) else {
+                continue;
+            };
+            let age = (now - incident.alert.time).num_minutes();
+            if age >= step.after_min as i64 {
+                incident.escalation_level += 1;
+                due.push((incident.alert.clone(), policy, incident.escalation_level));
+            }
+        }
+    }
+
+    for (alert, policy, level) in due {
+        let step = &policy.step[level - 1];
+        info!("[escalation] incident {}: {}", alert.id, header(policy, level, TemplateFormat::Text));
+        for target in &step.targets {
+            match target.parse::<Target>() {
+                Ok(target) => notify(target, &alert, policy, level).await,
+                Err(e) => warn!("[escalation] {}", e),
+            }
+        }
+    }
+}
+
+/// Escalation notice with the policy name as inline code of the message format
+fn header(policy: &Escalation, level: usize, format: TemplateFormat) -> String {
+    let name = match format {
+        TemplateFormat::Html => format!("<code>{}</code>", html::escape(&policy.name)),
+        TemplateFormat::Markdown => format!("`{}`", markdown::escape_code(&policy.name)),
+        TemplateFormat::Text => policy.name.clone(),
+    };
+    let after_min = policy.step[level - 1].after_min;
+    let rest = format!(" tier {}: not acknowledged for {} min", level, after_min);
+    format!("⏫ Escalation {}{}", name, escape(&rest, format))
+}
+
+async fn notify(target: Target, alert: &Alert, policy: &Escalation, level: usize) {
+    match target {
+        Target::Chat { chat_id, thread_id } => {
+            let Some(bot) = BOT.get() else {
+                warn!("[escalation] telegram bot is not running");
+                return;
+            };
+            let rendered = template::render("telegram", alert, "");
+            let rendered = Rendered {
+                text: format!("{}\n{}", header(policy, level, rendered.format), rendered.text),
+                format: rendered.format,
+            };
+            let thread_id = thread_id.map(|id| ThreadId(MessageId(id)));
+            send_alert(bot, chat_id, thread_id, None, alert, &rendered).await;
+        }
+        Target::Oncall(schedule) => {
+            let Some(bot) = BOT.get() else {
+                warn!("[escalation] telegram bot is not running");
+                return;
+            };
+            let rendered = template::render("telegram", alert, "");
+            let rendered = Rendered {
+                text: format!("{}\n{}", header(policy, level, rendered.format), rendered.text),
+                format: rendered.format,
+            };
+            send_oncall_dm(bot, &schedule, alert, &rendered).await;
+        }
+        Target::Hook(url) => {
+            let rendered = template::render("webhook", alert, "");
+            let payload = serde_json::json!({
+                "escalation": header(policy, level, TemplateFormat::Text),
+                "text": rendered.text,
+                "alert": alert,
+            });
+            post(url, payload.to_string()).await;
+        }
+        Target::Mail(address) => {
+            let rendered = template::render("mail", alert, "");
+            let subject = format!("[botte] escalation: {}", incident_title(alert));
+            let body = format!("{}\n\n{}", header(policy, level, TemplateFormat::Text), rendered.text);
+            if let Err(e) = send_mail(&address, &subject, body).await {
+                error!("[escalation] mail to {} failed: {}", address, e);
+            }
+        }
+    }
+}
+
+fn incident_title(alert: &Alert) -> String {
+    if alert.title.is_empty() {
+        alert.body.lines().next().unwrap_or_default().to_string()
+    } else {
+        alert.title.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_follows_the_format() {
+        let policy: Escalation = toml::from_str(
+            r#"
+            name = "db<prod>"
+            [[step]]
+            after_min = 15
+            targets = ["chat:-100"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            header(&policy, 1, TemplateFormat::Html),
+            "⏫ Escalation <code>db&lt;prod&gt;</code> tier 1: not acknowledged for 15 min"
+        );
+        assert_eq!(
+            header(&policy, 1, TemplateFormat::Text),
+            "⏫ Escalation db<prod> tier 1: not acknowledged for 15 min"
+        );
+        assert!(header(&policy, 1, TemplateFormat::Markdown).starts_with("⏫ Escalation `db<prod>`"));
+    }
+}
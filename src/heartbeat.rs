@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::bail;
+use chrono::{DateTime, Local};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+
+use crate::alert::Alert;
+use crate::boardcast::BROADCAST_SENDER;
+use crate::config::{Heartbeat, CONFIG};
+use crate::util::{format_duration, parse_duration};
+use crate::G_TOKIO_RUNTIME;
+
+const TICK: Duration = Duration::from_secs(30);
+
+/// Last ping of every heartbeat by name
+pub static BEATS: Lazy<Mutex<BTreeMap<String, Beat>>> = Lazy::new(|| {
+    let now = Local::now();
+    let beats = CONFIG
+        .heartbeat
+        .iter()
+        .map(|hb| {
+            let beat = Beat {
+                last: None,
+                since: now,
+                missed: false,
+            };
+            (hb.name.clone(), beat)
+        })
+        .collect();
+    Mutex::new(beats)
+});
+
+#[derive(Debug, Clone)]
+pub struct Beat {
+    pub last: Option<DateTime<Local>>,
+    /// last ping, or botte's start before the first one
+    pub since: DateTime<Local>,
+    pub missed: bool,
+}
+
+pub fn validate() -> anyhow::Result<()> {
+    for hb in &CONFIG.heartbeat {
+        for value in [&hb.interval, &hb.grace] {
+            if parse_duration(value).is_none() {
+                bail!("[heartbeat] invalid duration `{}` of {}", value, hb.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn find(name: &str) -> Option<&'static Heartbeat> {
+    CONFIG.heartbeat.iter().find(|hb| hb.name == name)
+}
+
+/// When the heartbeat is considered missed
+pub fn deadline(hb: &Heartbeat, beat: &Beat) -> DateTime<Local> {
+    let interval = parse_duration(&hb.interval).unwrap_or_default();
+    let grace = parse_duration(&hb.grace).unwrap_or_default();
+    beat.since + interval + grace
+}
+
+fn title(hb: &Heartbeat) -> String {
+    format!("heartbeat {} missed", hb.name)
+}
+
+async fn send(alert: Alert) {
+    if let Err(e) = BROADCAST_SENDER.get().unwrap().send(alert).await {
+        error!("[heartbeat] failed to send alert: {}", e);
+    }
+}
+
+/// Record a ping, `false` for an unknown heartbeat
+pub async fn beat(name: &str) -> bool {
+    let Some(hb) = find(name) else {
+        return false;
+    };
+    let now = Local::now();
+    let recovered = {
+        let mut beats = BEATS.lock().unwrap();
+        let Some(beat) = beats.get_mut(name) else {
+            return false;
+        };
+        let recovered = beat.missed.then(|| now - beat.since);
+        *beat = Beat {
+            last: Some(now),
+            since: now,
+            missed: false,
+        };
+        recovered
+    };
+    info!("[heartbeat] {} pinged", name);
+    if let Some(silent) = recovered {
+        let body = format!("{} is back after {} without ping", hb.name, format_duration(silent));
+        send(Alert::recovery("heartbeat", title(hb), body).with_label("heartbeat", &hb.name)).await;
+    }
+    true
+}
+
+/// `{"ping": "<name>"}` posted to the alert api counts as a ping of that heartbeat
+pub async fn beat_json(body: &str) {
+    let name = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|j| j.get("ping")?.as_str().map(str::to_string));
+    if let Some(name) = name
+        && find(&name).is_some()
+    {
+        beat(&name).await;
+    }
+}
+
+fn missed() -> Vec<Alert> {
+    let now = Local::now();
+    let mut beats = BEATS.lock().unwrap();
+    let mut alerts = vec![];
+    for hb in &CONFIG.heartbeat {
+        let Some(beat) = beats.get_mut(&hb.name) else {
+            continue;
+        };
+        if beat.missed || now <= deadline(hb, beat) {
+            continue;
+        }
+        beat.missed = true;
+        let last = match beat.last {
+            Some(last) => format!("last ping {} ago", format_duration(now - last)),
+            None => format!("no ping since botte started {} ago", format_duration(now - beat.since)),
+        };
+        let body = format!("{} expected every {}, {}", hb.name, hb.interval, last);
+        alerts.push(
+            Alert::new("heartbeat", body)
+                .with_title(title(hb))
+                .with_severity(hb.severity)
+                .with_label("heartbeat", &hb.name),
+        );
+    }
+    alerts
+}
+
+pub fn run_heartbeat() {
+    if CONFIG.heartbeat.is_empty() {
+        return;
+    }
+    info!("[heartbeat] expecting {} heartbeats", CONFIG.heartbeat.len());
+    Lazy::force(&BEATS);
+    G_TOKIO_RUNTIME.spawn(async {
+        let mut ticker = tokio::time::interval(TICK);
+        loop {
+            ticker.tick().await;
+            for alert in missed() {
+                warn!("[heartbeat] {}", alert.body);
+                send(alert).await;
+            }
+        }
+    });
+}
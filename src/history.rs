@@ -0,0 +1,252 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::bail;
+use chrono::{DateTime, Local};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use plotters::prelude::*;
+use plotters::style::FontStyle;
+use serde::{Deserialize, Serialize};
+
+use crate::config::CONFIG;
+use crate::monitor::{Sample, Sampler};
+use crate::util::parse_duration;
+use crate::G_TOKIO_RUNTIME;
+
+const WIDTH: u32 = 1000;
+const HEIGHT: u32 = 500;
+/// the history file is rewritten without expired points this often
+const COMPACT_EVERY: Duration = Duration::from_secs(24 * 3600);
+
+const FONTS: [&str; 5] = [
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+];
+
+static HISTORY: Lazy<Mutex<VecDeque<Point>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+
+/// Whether a font could be registered for chart labels
+static FONT: Lazy<bool> = Lazy::new(|| {
+    let candidates = CONFIG.history.font.iter().cloned().chain(FONTS.iter().map(Into::into));
+    for path in candidates {
+        let Ok(bytes) = std::fs::read(&path) else {
+            continue;
+        };
+        // plotters keeps fonts for the whole process
+        if plotters::style::register_font("sans-serif", FontStyle::Normal, Vec::leak(bytes)).is_ok() {
+            info!("[history] chart font {}", path.display());
+            return true;
+        }
+    }
+    warn!("[history] no font found, charts are drawn without labels");
+    false
+});
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Point {
+    pub time: DateTime<Local>,
+    pub cpu: f64,
+    pub mem: f64,
+    pub swap: f64,
+    pub disk: f64,
+    #[serde(default)]
+    pub inode: f64,
+    pub load: f64,
+    pub net_rx: f64,
+    pub net_tx: f64,
+}
+
+impl Point {
+    fn new(sample: &Sample) -> Self {
+        Point {
+            time: Local::now(),
+            cpu: sample.cpu,
+            mem: sample.mem,
+            swap: sample.swap,
+            disk: sample.get("disk"),
+            inode: sample.get("inode"),
+            load: sample.load,
+            net_rx: sample.net_rx,
+            net_tx: sample.net_tx,
+        }
+    }
+
+    pub fn get(&self, metric: &str) -> Option<f64> {
+        match metric {
+            "cpu" => Some(self.cpu),
+            "mem" => Some(self.mem),
+            "swap" => Some(self.swap),
+            "disk" => Some(self.disk),
+            "inode" => Some(self.inode),
+            "load" => Some(self.load),
+            "net_rx" => Some(self.net_rx),
+            "net_tx" => Some(self.net_tx),
+            _ => None,
+        }
+    }
+}
+
+pub fn validate() -> anyhow::Result<()> {
+    if parse_duration(&CONFIG.history.retention).is_none() {
+        bail!("[history] invalid retention `{}`", CONFIG.history.retention);
+    }
+    Ok(())
+}
+
+pub fn retention() -> chrono::Duration {
+    parse_duration(&CONFIG.history.retention).unwrap_or(chrono::Duration::days(7))
+}
+
+/// Values of a metric within the last `range`, at most the retention window
+pub fn series(metric: &str, range: chrono::Duration) -> Vec<(DateTime<Local>, f64)> {
+    let Some(since) = Local::now().checked_sub_signed(range.min(retention())) else {
+        return vec![];
+    };
+    HISTORY
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|p| p.time >= since)
+        .filter_map(|p| Some((p.time, p.get(metric)?)))
+        .collect()
+}
+
+/// Line chart of the series as png
+pub fn chart(title: &str, unit: &str, series: &[(DateTime<Local>, f64)]) -> Result<Vec<u8>, String> {
+    let (Some(first), Some(last)) = (series.first(), series.last()) else {
+        return Err("no data recorded yet".to_string());
+    };
+    let labels = *FONT;
+    let max = series.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let top = if unit == "%" { 100.0 } else { (max * 1.1).max(1.0) };
+    let mut buf = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buf, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE).map_err(|e| e.to_string())?;
+        let mut builder = ChartBuilder::on(&root);
+        builder.margin(20);
+        if labels {
+            builder
+                .caption(title, ("sans-serif", 24))
+                .x_label_area_size(30)
+                .y_label_area_size(60);
+        }
+        let mut chart = builder
+            .build_cartesian_2d(first.0..last.0.max(first.0 + chrono::Duration::minutes(1)), 0f64..top)
+            .map_err(|e| e.to_string())?;
+        let span = last.0 - first.0;
+        let time_fmt = if span > chrono::Duration::days(1) { "%m-%d %H:%M" } else { "%H:%M" };
+        let x_fmt = |t: &DateTime<Local>| t.format(time_fmt).to_string();
+        let y_fmt = |v: &f64| format!("{:.1}{}", v, unit);
+        chart
+            .configure_mesh()
+            .x_labels(if labels { 8 } else { 0 })
+            .y_labels(if labels { 6 } else { 0 })
+            .x_label_formatter(&x_fmt)
+            .y_label_formatter(&y_fmt)
+            .light_line_style(WHITE.mix(0.0))
+            .draw()
+            .map_err(|e| e.to_string())?;
+        chart
+            .draw_series(AreaSeries::new(series.iter().copied(), 0.0, BLUE.mix(0.15)).border_style(BLUE.stroke_width(2)))
+            .map_err(|e| e.to_string())?;
+        root.present().map_err(|e| e.to_string())?;
+    }
+
+    let mut png = vec![];
+    let mut encoder = png::Encoder::new(&mut png, WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&buf).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+/// Load unexpired points and rewrite the file without the others
+fn load() -> std::io::Result<()> {
+    let path = &CONFIG.history.path;
+    let since = Local::now() - retention();
+    let points: VecDeque<Point> = match std::fs::read_to_string(path) {
+        Ok(text) => text
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Point>(line).ok())
+            .filter(|p| p.time >= since)
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => VecDeque::new(),
+        Err(e) => return Err(e),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut text = String::new();
+    for p in &points {
+        text.push_str(&serde_json::to_string(p).unwrap_or_default());
+        text.push('\n');
+    }
+    std::fs::write(path, text)?;
+    info!("[history] loaded {} points from {}", points.len(), path.display());
+    *HISTORY.lock().unwrap() = points;
+    Ok(())
+}
+
+fn record(point: Point) -> std::io::Result<()> {
+    let line = serde_json::to_string(&point).unwrap_or_default();
+    {
+        let since = Local::now() - retention();
+        let mut history = HISTORY.lock().unwrap();
+        history.push_back(point);
+        while history.front().is_some_and(|p| p.time < since) {
+            history.pop_front();
+        }
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&CONFIG.history.path)?;
+    writeln!(file, "{}", line)
+}
+
+/// File IO off the async workers
+async fn blocking(f: impl FnOnce() -> std::io::Result<()> + Send + 'static) -> std::io::Result<()> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+}
+
+pub fn run_history() {
+    if !CONFIG.history.enable {
+        return;
+    }
+    G_TOKIO_RUNTIME.spawn(async {
+        if let Err(e) = blocking(load).await {
+            error!("[history] failed to load {}: {}", CONFIG.history.path.display(), e);
+        }
+        let mut sampler = Sampler::new();
+        let mut compacted = std::time::Instant::now();
+        let mut ticker = tokio::time::interval(Duration::from_secs(CONFIG.history.interval_secs.max(1)));
+        // cpu usage needs two refreshes some time apart
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let sample;
+            (sampler, sample) = sampler.sample_blocking().await;
+            let point = Point::new(&sample);
+            if let Err(e) = blocking(move || record(point)).await {
+                error!("[history] failed to record: {}", e);
+            }
+            if compacted.elapsed() > COMPACT_EVERY {
+                compacted = std::time::Instant::now();
+                if let Err(e) = blocking(load).await {
+                    error!("[history] failed to compact: {}", e);
+                }
+            }
+        }
+    });
+}
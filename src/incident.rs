@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Duration, Local};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use teloxide::types::{ChatId, MessageId};
+
+use crate::alert::Alert;
+use crate::config::TemplateFormat;
+
+/// Keep the latest incidents only
+const MAX_INCIDENTS: usize = 1000;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+pub static INCIDENTS: Lazy<Mutex<BTreeMap<u64, Incident>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+pub static SILENCES: Lazy<Mutex<Vec<Silence>>> = Lazy::new(|| Mutex::new(vec![]));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IncidentStatus {
+    Firing,
+    Acked,
+    Resolved,
+}
+
+/// A telegram message showing the incident, edited when its state changes
+#[derive(Debug, Clone)]
+pub struct SentMessage {
+    pub chat_id: ChatId,
+    pub message_id: MessageId,
+    pub text: String,
+    pub format: TemplateFormat,
+    /// index of the route in `[[telegram.route]]`, for its action buttons
+    pub route: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Incident {
+    pub id: u64,
+    pub alert: Alert,
+    pub status: IncidentStatus,
+    pub acked_by: Option<String>,
+    pub acked_at: Option<DateTime<Local>>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<DateTime<Local>>,
+    pub silenced_by: Option<String>,
+    pub silenced_until: Option<DateTime<Local>>,
+    /// escalation steps already notified
+    pub escalation_level: usize,
+    #[serde(skip)]
+    pub messages: Vec<SentMessage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Silence {
+    pub fingerprint: String,
+    pub by: String,
+    pub until: DateTime<Local>,
+}
+
+/// Alerts with the same source and title are considered the same alert
+pub fn fingerprint(alert: &Alert) -> String {
+    let title = if alert.title.is_empty() {
+        alert.body.lines().next().unwrap_or_default()
+    } else {
+        &alert.title
+    };
+    format!("{}:{}", alert.source, title)
+}
+
+pub fn is_silenced(alert: &Alert) -> bool {
+    let now = Local::now();
+    let fp = fingerprint(alert);
+    let mut silences = SILENCES.lock().unwrap();
+    silences.retain(|s| s.until > now);
+    silences.iter().any(|s| s.fingerprint == fp)
+}
+
+/// Register a new incident for the alert and assign its id
+pub fn open(alert: &mut Alert) -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    alert.id = id;
+    // a recovery notice has nothing left to handle
+    let recovery = alert.is_recovery();
+    let incident = Incident {
+        id,
+        alert: alert.clone(),
+        status: if recovery { IncidentStatus::Resolved } else { IncidentStatus::Firing },
+        acked_by: None,
+        acked_at: None,
+        resolved_by: recovery.then(|| alert.source.clone()),
+        resolved_at: recovery.then(Local::now),
+        silenced_by: None,
+        silenced_until: None,
+        escalation_level: 0,
+        messages: vec![],
+    };
+    let mut incidents = INCIDENTS.lock().unwrap();
+    incidents.insert(id, incident);
+    while incidents.len() > MAX_INCIDENTS {
+        incidents.pop_first();
+    }
+    id
+}
+
+pub fn attach_message(id: u64, message: SentMessage) {
+    if let Some(incident) = INCIDENTS.lock().unwrap().get_mut(&id) {
+        incident.messages.push(message);
+    }
+}
+
+pub fn get(id: u64) -> Option<Incident> {
+    INCIDENTS.lock().unwrap().get(&id).cloned()
+}
+
+pub fn list() -> Vec<Incident> {
+    INCIDENTS.lock().unwrap().values().rev().cloned().collect()
+}
+
+pub fn ack(id: u64, user: &str) -> Option<Incident> {
+    let mut incidents = INCIDENTS.lock().unwrap();
+    let incident = incidents.get_mut(&id)?;
+    if incident.status == IncidentStatus::Firing {
+        incident.status = IncidentStatus::Acked;
+        incident.acked_by = Some(user.to_string());
+        incident.acked_at = Some(Local::now());
+    }
+    Some(incident.clone())
+}
+
+pub fn resolve(id: u64, user: &str) -> Option<Incident> {
+    let mut incidents = INCIDENTS.lock().unwrap();
+    let incident = incidents.get_mut(&id)?;
+    if incident.status != IncidentStatus::Resolved {
+        incident.status = IncidentStatus::Resolved;
+        incident.resolved_by = Some(user.to_string());
+        incident.resolved_at = Some(Local::now());
+    }
+    Some(incident.clone())
+}
+
+/// Resolve the open incidents the recovery alert refers to
+pub fn resolve_recovered(recovery: &Alert) -> Vec<Incident> {
+    let fp = fingerprint(recovery);
+    let now = Local::now();
+    let mut incidents = INCIDENTS.lock().unwrap();
+    incidents
+        .values_mut()
+        .filter(|i| i.status != IncidentStatus::Resolved && fingerprint(&i.alert) == fp)
+        .map(|incident| {
+            incident.status = IncidentStatus::Resolved;
+            incident.resolved_by = Some(recovery.source.clone());
+            incident.resolved_at = Some(now);
+            incident.clone()
+        })
+        .collect()
+}
+
+/// Silence the alert of the incident, following alerts with the same fingerprint are dropped
+pub fn silence(id: u64, user: &str, duration: Duration) -> Option<Incident> {
+    let until = Local::now() + duration;
+    let incident = {
+        let mut incidents = INCIDENTS.lock().unwrap();
+        let incident = incidents.get_mut(&id)?;
+        incident.silenced_by = Some(user.to_string());
+        incident.silenced_until = Some(until);
+        incident.clone()
+    };
+    let fp = fingerprint(&incident.alert);
+    let mut silences = SILENCES.lock().unwrap();
+    silences.retain(|s| s.fingerprint != fp);
+    silences.push(Silence {
+        fingerprint: fp,
+        by: user.to_string(),
+        until,
+    });
+    Some(incident)
+}
@@ -1,11 +1,32 @@
 // basic
 pub mod config;
+pub mod util;
+pub mod audit;
+pub mod alert;
+pub mod template;
 
 // server to fetch msg
 pub mod api;
 pub mod mail;
+pub mod logtail;
+pub mod checks;
+pub mod certs;
+pub mod heartbeat;
+pub mod probe;
 // transport layer
 pub mod boardcast;
+pub mod incident;
+pub mod escalation;
+pub mod oncall;
+pub mod exec;
+pub mod logs;
+pub mod systemd;
+pub mod docker;
+pub mod disk;
+pub mod monitor;
+pub mod history;
+pub mod watchdog;
+pub mod runbook;
 
 // client to push msg
 pub mod webhook;
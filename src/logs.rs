@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::config::CONFIG;
+
+/// Lines returned by `/tail` at most
+pub const MAX_TAIL: usize = 1000;
+/// Matches returned by `/grep` at most
+pub const MAX_MATCHES: usize = 200;
+
+/// Configured aliases plus `botte` for our own log
+pub fn aliases() -> BTreeMap<String, PathBuf> {
+    let mut aliases = BTreeMap::from([("botte".to_string(), PathBuf::from("logs/botte.log"))]);
+    aliases.extend(CONFIG.logs.iter().map(|(k, v)| (k.clone(), v.clone())));
+    aliases
+}
+
+/// The file of an alias. Rolling logs like `botte.log.2025-01-01` resolve
+/// to the most recently modified file starting with the configured name.
+pub fn resolve(alias: &str) -> Option<PathBuf> {
+    let path = aliases().remove(alias)?;
+    if path.is_file() {
+        return Some(path);
+    }
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&name))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .filter(|(_, path)| path.is_file())
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Last `n` lines, reading the file backwards
+pub fn tail(path: &Path, n: usize) -> io::Result<String> {
+    const BLOCK: u64 = 64 * 1024;
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut pos = len;
+    let mut buf = vec![];
+    while pos > 0 && buf.iter().filter(|b| **b == b'\n').count() <= n {
+        let read = BLOCK.min(pos);
+        pos -= read;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut block = vec![0; read as usize];
+        file.read_exact(&mut block)?;
+        block.extend_from_slice(&buf);
+        buf = block;
+    }
+    let text = String::from_utf8_lossy(&buf);
+    let lines: Vec<&str> = text.lines().collect();
+    Ok(lines[lines.len().saturating_sub(n)..].join("\n"))
+}
+
+/// Matching lines with `context` lines around them, `grep -n -C` style
+pub fn grep(path: &Path, re: &Regex, context: usize) -> io::Result<(String, usize)> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut out = vec![];
+    let mut last_printed = 0;
+    let mut emit = |number: usize, sep: char, line: &str| {
+        if last_printed > 0 && number > last_printed + 1 {
+            out.push("--".to_string());
+        }
+        out.push(format!("{}{}{}", number, sep, line));
+        last_printed = number;
+    };
+    let mut before: VecDeque<(usize, String)> = VecDeque::new();
+    let mut after = 0;
+    let mut matches = 0;
+    for (i, line) in reader.split(b'\n').enumerate() {
+        let number = i + 1;
+        let line = String::from_utf8_lossy(&line?).trim_end_matches('\r').to_string();
+        if re.is_match(&line) {
+            if matches == MAX_MATCHES {
+                break;
+            }
+            matches += 1;
+            for (n, l) in before.drain(..) {
+                emit(n, '-', &l);
+            }
+            emit(number, ':', &line);
+            after = context;
+        } else if after > 0 {
+            emit(number, '-', &line);
+            after -= 1;
+        } else if context > 0 {
+            before.push_back((number, line));
+            if before.len() > context {
+                before.pop_front();
+            }
+        }
+    }
+    Ok((out.join("\n"), matches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("botte-{}-{}.log", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn tail_lines() {
+        let text: String = (1..=1000).map(|i| format!("line {}\n", i)).collect();
+        let path = file("tail", &text);
+        assert_eq!(tail(&path, 2).unwrap(), "line 999\nline 1000");
+        assert_eq!(tail(&path, 5000).unwrap().lines().count(), 1000);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn grep_with_context() {
+        let path = file("grep", "a\nerror 1\nb\nc\nd\ne\nerror 2\nf\n");
+        let re = Regex::new("error").unwrap();
+        let (out, matches) = grep(&path, &re, 1).unwrap();
+        assert_eq!(matches, 2);
+        assert_eq!(out, "1-a\n2:error 1\n3-b\n--\n6-e\n7:error 2\n8-f");
+        let (out, _) = grep(&path, &re, 0).unwrap();
+        assert_eq!(out, "2:error 1\n--\n7:error 2");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use std::io::SeekFrom;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use log::{error, info, warn};
+use regex::Regex;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::alert::Alert;
+use crate::boardcast::BROADCAST_SENDER;
+use crate::config::{LogRule, LogTail, CONFIG};
+use crate::util::parse_duration;
+use crate::G_TOKIO_RUNTIME;
+
+pub fn validate() -> anyhow::Result<()> {
+    for tail in &CONFIG.logtail {
+        for rule in &tail.rule {
+            Regex::new(&rule.regex)
+                .map_err(|e| anyhow::anyhow!("[logtail] rule `{}` of {}: {}", rule.name, tail.path.display(), e))?;
+            if let Some(window) = &rule.rate_limit
+                && parse_duration(window).is_none()
+            {
+                bail!("[logtail] invalid rate_limit `{}` of rule `{}`", window, rule.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Follows a file like `tail -F`: survives rotation by rename and truncation
+struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    inode: u64,
+    offset: u64,
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    async fn new(path: &Path, from_start: bool) -> Self {
+        let mut follower = Follower {
+            path: path.to_path_buf(),
+            file: None,
+            inode: 0,
+            offset: 0,
+            partial: vec![],
+        };
+        follower.reopen().await;
+        if !from_start && let Ok(meta) = tokio::fs::metadata(path).await {
+            follower.offset = meta.len();
+        }
+        follower
+    }
+
+    async fn reopen(&mut self) {
+        self.file = File::open(&self.path).await.ok();
+        self.inode = match &self.file {
+            Some(file) => file.metadata().await.map(|m| m.ino()).unwrap_or(0),
+            None => 0,
+        };
+        self.offset = 0;
+        self.partial.clear();
+    }
+
+    /// Complete lines appended since the last poll
+    async fn poll(&mut self) -> Vec<String> {
+        let mut lines = vec![];
+        let meta = tokio::fs::metadata(&self.path).await.ok();
+        match meta {
+            // rotated: finish the old file, then start over with the new one
+            Some(meta) if meta.ino() != self.inode => {
+                lines.extend(self.read().await);
+                info!("[logtail] {} rotated", self.path.display());
+                self.reopen().await;
+            }
+            Some(meta) if meta.len() < self.offset => {
+                info!("[logtail] {} truncated", self.path.display());
+                self.offset = 0;
+                self.partial.clear();
+            }
+            _ => {}
+        }
+        lines.extend(self.read().await);
+        lines
+    }
+
+    async fn read(&mut self) -> Vec<String> {
+        let Some(file) = &mut self.file else {
+            return vec![];
+        };
+        let mut buf = vec![];
+        let read = async {
+            file.seek(SeekFrom::Start(self.offset)).await?;
+            file.read_to_end(&mut buf).await
+        };
+        if let Err(e) = read.await {
+            warn!("[logtail] failed to read {}: {}", self.path.display(), e);
+            return vec![];
+        }
+        self.offset += buf.len() as u64;
+        self.partial.extend_from_slice(&buf);
+        let Some(end) = self.partial.iter().rposition(|b| *b == b'\n') else {
+            return vec![];
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        String::from_utf8_lossy(&complete)
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+}
+
+/// A rule with its compiled regex and rate limit window
+struct Matcher {
+    rule: &'static LogRule,
+    re: Regex,
+    window: Option<Duration>,
+    last_sent: Option<Instant>,
+    suppressed: usize,
+}
+
+impl Matcher {
+    fn new(rule: &'static LogRule) -> Option<Self> {
+        Some(Matcher {
+            rule,
+            re: Regex::new(&rule.regex).ok()?,
+            window: rule
+                .rate_limit
+                .as_deref()
+                .and_then(parse_duration)
+                .and_then(|d| d.to_std().ok()),
+            last_sent: None,
+            suppressed: 0,
+        })
+    }
+
+    fn check(&mut self, path: &Path, line: &str) -> Option<Alert> {
+        let caps = self.re.captures(line)?;
+        if let (Some(window), Some(last)) = (self.window, self.last_sent)
+            && last.elapsed() < window
+        {
+            self.suppressed += 1;
+            return None;
+        }
+        let expand = |template: &str| {
+            let mut out = String::new();
+            caps.expand(template, &mut out);
+            out
+        };
+        let title = expand(self.rule.title.as_deref().unwrap_or(&self.rule.name));
+        let mut body = self.rule.body.as_deref().map(expand).unwrap_or_else(|| line.to_string());
+        if self.suppressed > 0 {
+            body.push_str(&format!("\n({} similar lines suppressed)", self.suppressed));
+        }
+        self.last_sent = Some(Instant::now());
+        self.suppressed = 0;
+        Some(
+            Alert::new("logtail", body)
+                .with_title(title)
+                .with_severity(self.rule.severity)
+                .with_label("file", path.display().to_string())
+                .with_label("rule", &self.rule.name),
+        )
+    }
+}
+
+async fn follow(tail: &'static LogTail) {
+    let mut follower = Follower::new(&tail.path, tail.from_start).await;
+    let mut matchers: Vec<Matcher> = tail.rule.iter().filter_map(Matcher::new).collect();
+    let mut ticker = tokio::time::interval(Duration::from_millis(tail.poll_ms.max(100)));
+    loop {
+        ticker.tick().await;
+        for line in follower.poll().await {
+            // first matching rule wins
+            let Some(alert) = matchers.iter_mut().find(|m| m.re.is_match(&line)).and_then(|m| m.check(&tail.path, &line)) else {
+                continue;
+            };
+            if let Err(e) = BROADCAST_SENDER.get().unwrap().send(alert).await {
+                error!("[logtail] failed to send alert: {}", e);
+            }
+        }
+    }
+}
+
+pub fn run_logtail() {
+    for tail in &CONFIG.logtail {
+        info!("[logtail] following {} with {} rules", tail.path.display(), tail.rule.len());
+        G_TOKIO_RUNTIME.spawn(follow(tail));
+    }
+}
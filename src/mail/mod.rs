@@ -1,3 +1,5 @@
+pub mod smtp;
+
 use async_imap::Client;
 use futures::StreamExt;
 use log::{error, info, warn};
@@ -8,7 +10,7 @@ use tokio_util::compat::TokioAsyncReadCompatExt;
 use chrono::{DateTime, Utc};
 
 use crate::{
-    boardcast::BROADCAST_SENDER, config::{self, CONFIG}, G_TOKIO_RUNTIME
+    alert::Alert, boardcast::BROADCAST_SENDER, bot::format::html_to_text, config::{self, CONFIG}, G_TOKIO_RUNTIME
 };
 
 use std::collections::HashMap;
@@ -81,7 +83,7 @@ pub async fn mail_client(mail: config::Mail) -> anyhow::Result<()> {
                     let to = mail.headers.get_first_value("To").unwrap_or_default();
                     let content = extract_body(&mail);
 
-                    let from_address = from.split('<').last().and_then(|s| s.split('>').next()).unwrap_or_default().trim();
+                    let from_address = from.split('<').next_back().and_then(|s| s.split('>').next()).unwrap_or_default().trim();
                     if filer_users.contains(&from_address.to_string()) {
                         // 获取邮件发送时间
                         let date_str = mail.headers.get_first_value("Date").unwrap_or_default();
@@ -122,7 +124,10 @@ pub async fn mail_client(mail: config::Mail) -> anyhow::Result<()> {
                         info!("[mail] Sub:[{}] marked as seen", subject);
                         to_mark_as_read.push(seq.to_string());
                         if let Some(tx) = BROADCAST_SENDER.get() {
-                            let ret = tx.send(content).await;
+                            let alert = Alert::new("mail", content)
+                                .with_title(subject.clone())
+                                .with_label("from", from_address);
+                            let ret = tx.send(alert).await;
                             if let Err(e) = ret {
                                 error!("[mail] Failed to send broadcast message: {}", e);
                             } else {
@@ -148,17 +153,29 @@ pub async fn mail_client(mail: config::Mail) -> anyhow::Result<()> {
 fn extract_body(parsed_mail: &mailparse::ParsedMail) -> String {
     if parsed_mail.subparts.is_empty() {
         // 如果没有子部分，直接返回正文
-        parsed_mail.get_body().unwrap_or_default()
+        let body = parsed_mail.get_body().unwrap_or_default();
+        if parsed_mail.ctype.mimetype == "text/html" {
+            html_to_text(&body)
+        } else {
+            body
+        }
     } else {
-        // 遍历子部分，查找 text/plain 或 text/html
-        for subpart in &parsed_mail.subparts {
-            if let Some(content_type) = subpart.headers.get_first_value("Content-Type") {
-                if content_type.contains("text/plain") || content_type.contains("text/html") {
-                    return subpart.get_body().unwrap_or_default();
-                }
-            }
+        // 遍历子部分，优先 text/plain, 其次 text/html 转为纯文本
+        let part = |kind: &str| {
+            parsed_mail.subparts.iter().find(|subpart| subpart.ctype.mimetype == kind)
+        };
+        if let Some(plain) = part("text/plain") {
+            return plain.get_body().unwrap_or_default();
+        }
+        if let Some(html) = part("text/html") {
+            return html_to_text(&html.get_body().unwrap_or_default());
         }
-        // 如果没有找到合适的子部分，返回空字符串
-        String::new()
+        // 递归查找 multipart/alternative 等嵌套部分
+        parsed_mail
+            .subparts
+            .iter()
+            .map(extract_body)
+            .find(|body| !body.is_empty())
+            .unwrap_or_default()
     }
 }
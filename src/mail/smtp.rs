@@ -0,0 +1,34 @@
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::info;
+
+use crate::config::CONFIG;
+
+/// Send a plain text mail through the `[smtp]` account
+pub async fn send_mail(to: &str, subject: &str, body: String) -> anyhow::Result<()> {
+    let smtp = CONFIG
+        .smtp
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("smtp is not configured"))?;
+    let services = smtp.smtp_service.split(":").collect::<Vec<&str>>();
+    let (host, port) = if services.len() == 2 {
+        (services[0].to_string(), services[1].parse::<u16>()?)
+    } else {
+        (smtp.smtp_service.clone(), 465) // Default SMTPS port
+    };
+
+    let message = Message::builder()
+        .from(smtp.email.parse()?)
+        .to(to.parse()?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)?;
+    let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?
+        .port(port)
+        .credentials(Credentials::new(smtp.email.clone(), smtp.passwd.clone()))
+        .build();
+    transport.send(message).await?;
+    info!("[smtp] mail sent to {}: {}", to, subject);
+    Ok(())
+}
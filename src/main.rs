@@ -1,8 +1,19 @@
+use botte::alert::{Alert, Severity};
 use botte::boardcast::init_channel;
 use botte::bot::{run_bots, BOTS_TX};
 use botte::config::CONFIG;
+use botte::escalation::run_escalation;
 use botte::api::run_serve;
 use botte::mail::run_mail;
+use botte::logtail::run_logtail;
+use botte::checks::run_checks;
+use botte::certs::run_certs;
+use botte::heartbeat::run_heartbeat;
+use botte::probe::run_probes;
+use botte::bot::report::run_reports;
+use botte::monitor::run_monitor;
+use botte::history::run_history;
+use botte::watchdog::run_watchdog;
 use botte::webhook::run_webhook;
 use log::info;
 
@@ -18,11 +29,21 @@ extern crate libc;
 fn enbale_server() {
     run_mail();
     run_serve();
+    run_logtail();
+    run_checks();
+    run_certs();
+    run_heartbeat();
+    run_probes();
 }
 
 fn enable_client() {
     run_bots();
     run_webhook();
+    run_escalation();
+    run_monitor();
+    run_history();
+    run_watchdog();
+    run_reports();
 }
 
 fn main() {
@@ -55,6 +76,20 @@ fn boot() -> anyhow::Result<WorkerGuard> {
     let _ = botte::config::CONFIG_PATH.set(args.config);
     let cfg = CONFIG.clone();
     println!("{:?}", cfg);
+    botte::template::init_templates()?;
+    botte::oncall::validate()?;
+    botte::escalation::validate()?;
+    botte::exec::validate()?;
+    botte::runbook::validate()?;
+    botte::monitor::validate()?;
+    botte::watchdog::validate()?;
+    botte::logtail::validate()?;
+    botte::checks::validate()?;
+    botte::heartbeat::validate()?;
+    botte::probe::validate()?;
+    botte::certs::validate()?;
+    botte::bot::report::validate()?;
+    botte::history::validate()?;
 
     let file_appender = tracing_appender::rolling::daily("logs", "botte.log");
     let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
@@ -90,7 +125,10 @@ pub fn enable_panic_hook() {
             println_panic_msg(&format!("panic occurred payload: {}", payload));
         }
         if let Some (bots) = BOTS_TX.get() {
-            let _ = bots.send(format!("ERROR: {}", panic_info.to_string()));
+            let alert = Alert::new("botte", format!("ERROR: {}", panic_info))
+                .with_title("botte panic")
+                .with_severity(Severity::Critical);
+            let _ = bots.send(alert);
         }
         println_panic_msg(&format!("panic occurred: {:?}", panic_info));
         default_hook(panic_info);
@@ -0,0 +1,303 @@
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use log::{error, info, warn};
+use sysinfo::{Networks, System};
+
+use crate::alert::Alert;
+use crate::boardcast::BROADCAST_SENDER;
+use crate::config::{Threshold, CONFIG};
+use crate::disk::{self, Mount};
+use crate::util::{format_duration, parse_duration};
+use crate::G_TOKIO_RUNTIME;
+
+pub const METRICS: [&str; 8] = ["cpu", "mem", "swap", "disk", "inode", "load", "net_rx", "net_tx"];
+
+pub fn validate() -> anyhow::Result<()> {
+    let mounts = disk::mounts();
+    for threshold in &CONFIG.monitor.threshold {
+        if !METRICS.contains(&threshold.metric.as_str()) {
+            bail!("[monitor] unknown metric `{}`, expect one of {:?}", threshold.metric, METRICS);
+        }
+        if parse_duration(&threshold.duration).is_none() {
+            bail!("[monitor] invalid duration `{}` of {}", threshold.duration, threshold.metric);
+        }
+        if threshold.mount.is_some() && !matches!(threshold.metric.as_str(), "disk" | "inode") {
+            bail!("[monitor] mount only applies to disk and inode thresholds, not {}", threshold.metric);
+        }
+        // mounts sharing a device are listed once, under the shortest mount point
+        if let Some(mount) = &threshold.mount
+            && !mounts.iter().any(|m| &m.mount == mount)
+        {
+            let known: Vec<&str> = mounts.iter().map(|m| m.mount.as_str()).collect();
+            bail!("[monitor] unknown mount `{}` of {}, expect one of {:?}", mount, threshold.metric, known);
+        }
+        if recover_level(threshold) > threshold.above {
+            bail!("[monitor] recover level of {} must not exceed {}", threshold.metric, threshold.above);
+        }
+    }
+    Ok(())
+}
+
+fn recover_level(threshold: &Threshold) -> f64 {
+    threshold.recover.unwrap_or(threshold.above * 0.9)
+}
+
+pub fn unit(metric: &str) -> &'static str {
+    match metric {
+        "load" => "",
+        "net_rx" | "net_tx" => " MB/s",
+        _ => "%",
+    }
+}
+
+/// One measurement of every metric
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    pub cpu: f64,
+    pub mem: f64,
+    pub swap: f64,
+    pub mounts: Vec<Mount>,
+    pub load: f64,
+    pub net_rx: f64,
+    pub net_tx: f64,
+}
+
+impl Sample {
+    pub fn get(&self, metric: &str) -> f64 {
+        self.value(metric, None).map_or(0.0, |(value, _)| value)
+    }
+
+    /// Value of the metric, with the mount point for disk and inode usage.
+    /// Without `mount` the fullest one is used, `None` when the mount or metric does not exist.
+    pub fn value(&self, metric: &str, mount: Option<&str>) -> Option<(f64, Option<&str>)> {
+        let usage = |m: &Mount| match metric {
+            "inode" => m.inode_percent(),
+            _ => Some(m.percent()),
+        };
+        let value = match metric {
+            "cpu" => self.cpu,
+            "mem" => self.mem,
+            "swap" => self.swap,
+            "load" => self.load,
+            "net_rx" => self.net_rx,
+            "net_tx" => self.net_tx,
+            "disk" | "inode" => {
+                let fullest = self
+                    .mounts
+                    .iter()
+                    .filter(|m| mount.is_none_or(|path| m.mount == path))
+                    .filter_map(|m| Some((usage(m)?, m.mount.as_str())))
+                    .max_by(|a, b| a.0.total_cmp(&b.0));
+                return match fullest {
+                    Some((value, mount)) => Some((value, Some(mount))),
+                    None => mount.is_none().then_some((0.0, None)),
+                };
+            }
+            _ => return None,
+        };
+        Some((value, None))
+    }
+}
+
+/// Keeps sysinfo state between samples, cpu usage and network rates are deltas
+pub struct Sampler {
+    system: System,
+    networks: Networks,
+    last: Instant,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        let mut system = System::new();
+        system.refresh_cpu_usage();
+        Sampler {
+            system,
+            networks: Networks::new_with_refreshed_list(),
+            last: Instant::now(),
+        }
+    }
+
+    pub fn sample(&mut self) -> Sample {
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+        self.networks.refresh(true);
+        let secs = self.last.elapsed().as_secs_f64().max(1.0);
+        self.last = Instant::now();
+
+        let percent = |used: u64, total: u64| if total == 0 { 0.0 } else { used as f64 / total as f64 * 100.0 };
+        let rate = |bytes: u64| bytes as f64 / 1_048_576.0 / secs;
+        Sample {
+            cpu: self.system.global_cpu_usage() as f64,
+            mem: percent(self.system.used_memory(), self.system.total_memory()),
+            swap: percent(self.system.used_swap(), self.system.total_swap()),
+            mounts: disk::mounts(),
+            load: System::load_average().one,
+            net_rx: rate(self.networks.values().map(|n| n.received()).sum()),
+            net_tx: rate(self.networks.values().map(|n| n.transmitted()).sum()),
+        }
+    }
+
+    /// `sample` off the async workers, statvfs blocks on a hung network mount
+    pub async fn sample_blocking(mut self) -> (Self, Sample) {
+        tokio::task::spawn_blocking(move || {
+            let sample = self.sample();
+            (self, sample)
+        })
+        .await
+        .expect("sampler panicked")
+    }
+}
+
+/// Breach tracking of one threshold
+#[derive(Debug, Default)]
+struct State {
+    above_since: Option<Instant>,
+    firing: bool,
+}
+
+fn title(threshold: &Threshold) -> String {
+    let metric = match &threshold.mount {
+        Some(mount) => format!("{} {}", threshold.metric, mount),
+        None => threshold.metric.clone(),
+    };
+    format!("{} above {}{}", metric, threshold.above, unit(&threshold.metric))
+}
+
+fn describe(threshold: &Threshold, value: f64, mount: Option<&str>) -> String {
+    let value = format!("{:.1}{}", value, unit(&threshold.metric));
+    match mount {
+        Some(mount) => format!("{} {} on {}", threshold.metric, value, mount),
+        None => format!("{} {}", threshold.metric, value),
+    }
+}
+
+/// Advance the state, returning the alert to send when it fires or recovers
+fn check(threshold: &Threshold, state: &mut State, sample: &Sample) -> Option<Alert> {
+    let Some((value, mount)) = sample.value(&threshold.metric, threshold.mount.as_deref()) else {
+        // the mount is gone, a firing alert would never see the value drop
+        let firing = state.firing;
+        *state = State::default();
+        let mount = threshold.mount.as_deref()?;
+        if !firing {
+            return None;
+        }
+        warn!("[monitor] {} vanished while {} was firing", mount, title(threshold));
+        let body = format!("{} is no longer mounted, {} not monitored", mount, threshold.metric);
+        return Some(Alert::recovery("monitor", title(threshold), body).with_label("metric", &threshold.metric));
+    };
+    let sustain = parse_duration(&threshold.duration)?.to_std().unwrap_or_default();
+    if state.firing {
+        if value >= recover_level(threshold) {
+            return None;
+        }
+        *state = State::default();
+        let body = format!(
+            "{}, back below {}{}",
+            describe(threshold, value, mount),
+            recover_level(threshold),
+            unit(&threshold.metric)
+        );
+        return Some(Alert::recovery("monitor", title(threshold), body).with_label("metric", &threshold.metric));
+    }
+    if value <= threshold.above {
+        state.above_since = None;
+        return None;
+    }
+    let since = *state.above_since.get_or_insert_with(Instant::now);
+    if since.elapsed() < sustain {
+        return None;
+    }
+    state.firing = true;
+    let elapsed = chrono::Duration::from_std(since.elapsed()).unwrap_or_default();
+    let body = format!(
+        "{} for {}, threshold {}{}",
+        describe(threshold, value, mount),
+        format_duration(elapsed),
+        threshold.above,
+        unit(&threshold.metric)
+    );
+    Some(
+        Alert::new("monitor", body)
+            .with_title(title(threshold))
+            .with_severity(threshold.severity)
+            .with_label("metric", &threshold.metric)
+            .with_label("value", format!("{:.1}", value)),
+    )
+}
+
+pub fn run_monitor() {
+    if CONFIG.monitor.threshold.is_empty() {
+        return;
+    }
+    info!("[monitor] watching {} thresholds", CONFIG.monitor.threshold.len());
+    G_TOKIO_RUNTIME.spawn(async {
+        let mut sampler = Sampler::new();
+        let mut states: Vec<State> = CONFIG.monitor.threshold.iter().map(|_| State::default()).collect();
+        let mut ticker = tokio::time::interval(Duration::from_secs(CONFIG.monitor.interval_secs.max(1)));
+        // cpu usage needs two refreshes some time apart
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let sample;
+            (sampler, sample) = sampler.sample_blocking().await;
+            for (threshold, state) in CONFIG.monitor.threshold.iter().zip(states.iter_mut()) {
+                let Some(alert) = check(threshold, state, &sample) else {
+                    continue;
+                };
+                warn!("[monitor] {}: {}", alert.title, alert.body);
+                if let Err(e) = BROADCAST_SENDER.get().unwrap().send(alert).await {
+                    error!("[monitor] failed to send alert: {}", e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(mount: &str, used: u64) -> Sample {
+        Sample {
+            mounts: vec![Mount {
+                mount: mount.to_string(),
+                device: "/dev/sdb1".to_string(),
+                fs: "ext4".to_string(),
+                total: 100,
+                used,
+                available: 100 - used,
+                inodes: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn vanished_mount_recovers() {
+        let threshold: Threshold = toml::from_str(
+            r#"
+            metric = "disk"
+            mount = "/data"
+            above = 90.0
+            for = "0s"
+            "#,
+        )
+        .unwrap();
+        let mut state = State::default();
+        let alert = check(&threshold, &mut state, &sample("/data", 95)).unwrap();
+        assert!(!alert.is_recovery());
+        assert!(state.firing);
+
+        let alert = check(&threshold, &mut state, &sample("/", 95)).unwrap();
+        assert!(alert.is_recovery());
+        assert!(!state.firing);
+        assert!(check(&threshold, &mut state, &sample("/", 95)).is_none());
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::Mutex;
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use teloxide::utils::{html, markdown};
+
+use crate::config::{Oncall, TelegramUser, TemplateFormat, CONFIG};
+
+const TIME_FMT: &str = "%Y-%m-%d %H:%M";
+
+/// Overrides added at runtime by `/override`
+pub static OVERRIDES: Lazy<Mutex<Vec<Shift>>> = Lazy::new(|| Mutex::new(vec![]));
+
+#[derive(Debug, Clone)]
+pub struct Shift {
+    pub schedule: String,
+    pub user: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+fn tz(schedule: &Oncall) -> anyhow::Result<Tz> {
+    schedule
+        .timezone
+        .parse::<Tz>()
+        .map_err(|e| anyhow!("oncall `{}`: {}", schedule.name, e))
+}
+
+fn parse_time(tz: Tz, s: &str) -> anyhow::Result<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(s, TIME_FMT)
+        .map_err(|e| anyhow!("invalid time `{}`, expect `{}`: {}", s, TIME_FMT, e))?;
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("time `{}` does not exist in {}", s, tz))
+}
+
+pub fn validate() -> anyhow::Result<()> {
+    for schedule in &CONFIG.oncall {
+        let tz = tz(schedule)?;
+        parse_time(tz, &schedule.start)?;
+        if schedule.users.is_empty() || schedule.rotation_days == 0 {
+            bail!("oncall `{}`: need users and a positive rotation_days", schedule.name);
+        }
+        let overrides = schedule.overrides.iter().map(|o| &o.user);
+        for user in schedule.users.iter().chain(overrides) {
+            if !CONFIG.telegram.users.contains_key(user) {
+                bail!("oncall `{}`: user `{}` is not defined in [telegram.users]", schedule.name, user);
+            }
+        }
+        for o in &schedule.overrides {
+            parse_time(tz, &o.start)?;
+            parse_time(tz, &o.end)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn schedule(name: &str) -> Option<&'static Oncall> {
+    CONFIG.oncall.iter().find(|s| s.name == name)
+}
+
+/// Shift of the rotation containing `at`
+fn rotation_shift(schedule: &Oncall, at: DateTime<Utc>) -> Option<Shift> {
+    let start = parse_time(tz(schedule).ok()?, &schedule.start).ok()?;
+    let period = Duration::days(schedule.rotation_days as i64);
+    let index = (at - start).num_seconds().div_euclid(period.num_seconds());
+    let user = &schedule.users[index.rem_euclid(schedule.users.len() as i64) as usize];
+    let shift_start = start + period * index as i32;
+    Some(Shift {
+        schedule: schedule.name.clone(),
+        user: user.clone(),
+        start: shift_start,
+        end: shift_start + period,
+    })
+}
+
+/// Runtime overrides win over configured overrides, latest first
+fn override_at(schedule: &Oncall, at: DateTime<Utc>) -> Option<Shift> {
+    let runtime = OVERRIDES
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|o| o.schedule == schedule.name && o.start <= at && at < o.end)
+        .cloned();
+    runtime.or_else(|| {
+        let tz = tz(schedule).ok()?;
+        schedule.overrides.iter().rev().find_map(|o| {
+            let (start, end) = (parse_time(tz, &o.start).ok()?, parse_time(tz, &o.end).ok()?);
+            (start <= at && at < end).then(|| Shift {
+                schedule: schedule.name.clone(),
+                user: o.user.clone(),
+                start,
+                end,
+            })
+        })
+    })
+}
+
+/// Who is on call at the time
+pub fn on_call(schedule: &Oncall, at: DateTime<Utc>) -> Option<Shift> {
+    override_at(schedule, at).or_else(|| rotation_shift(schedule, at))
+}
+
+/// The shift after the current one
+pub fn next_shift(schedule: &Oncall, now: DateTime<Utc>) -> Option<Shift> {
+    let current = on_call(schedule, now)?;
+    on_call(schedule, current.end)
+}
+
+/// `None` when the end of the override is out of range
+pub fn add_override(schedule: &Oncall, user: &str, duration: Duration) -> Option<Shift> {
+    let now = Utc::now();
+    let shift = Shift {
+        schedule: schedule.name.clone(),
+        user: user.to_string(),
+        start: now,
+        end: now.checked_add_signed(duration)?,
+    };
+    let mut overrides = OVERRIDES.lock().unwrap();
+    overrides.retain(|o| o.end > now);
+    overrides.push(shift.clone());
+    Some(shift)
+}
+
+/// Find a user by name or `@username`
+pub fn find_user(name: &str) -> Option<(&'static String, &'static TelegramUser)> {
+    let username = name.trim_start_matches('@');
+    CONFIG
+        .telegram
+        .users
+        .iter()
+        .find(|(n, u)| n.as_str() == name || u.username.as_deref() == Some(username))
+}
+
+pub fn user(name: &str) -> Option<&'static TelegramUser> {
+    CONFIG.telegram.users.get(name)
+}
+
+/// Mention that notifies the user in a telegram chat
+pub fn mention(name: &str, format: TemplateFormat) -> String {
+    let Some(user) = user(name) else {
+        return name.to_string();
+    };
+    match (format, &user.username) {
+        (TemplateFormat::Text, Some(username)) => format!("@{}", username),
+        (TemplateFormat::Text, None) => name.to_string(),
+        (TemplateFormat::Html, _) => format!("<a href=\"tg://user?id={}\">{}</a>", user.id, html::escape(name)),
+        (TemplateFormat::Markdown, _) => format!("[{}](tg://user?id={})", markdown::escape(name), user.id),
+    }
+}
+
+pub fn format_shift(schedule: &Oncall, shift: &Shift) -> String {
+    let tz = tz(schedule).unwrap_or(Tz::UTC);
+    format!(
+        "{} → {} ({})",
+        shift.start.with_timezone(&tz).format(TIME_FMT),
+        shift.end.with_timezone(&tz).format(TIME_FMT),
+        tz
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(name: &str) -> Oncall {
+        toml::from_str(&format!(
+            r#"
+            name = "{}"
+            timezone = "Asia/Shanghai"
+            start = "2025-01-06 09:00"
+            rotation_days = 7
+            users = ["alice", "bob", "carol"]
+            [[override]]
+            user = "dave"
+            start = "2025-01-20 09:00"
+            end = "2025-01-21 09:00"
+            "#,
+            name
+        ))
+        .unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        parse_time(Tz::UTC, s).unwrap()
+    }
+
+    #[test]
+    fn start_is_local_time() {
+        assert_eq!(parse_time(Tz::Asia__Shanghai, "2025-01-06 09:00").unwrap(), utc("2025-01-06 01:00"));
+        assert!(parse_time(Tz::UTC, "2025-01-06").is_err());
+    }
+
+    #[test]
+    fn rotation() {
+        let s = schedule("rotation");
+        let at = |t| rotation_shift(&s, utc(t)).unwrap();
+        assert_eq!(at("2025-01-06 01:00").user, "alice");
+        assert_eq!(at("2025-01-13 00:59").user, "alice");
+        assert_eq!(at("2025-01-13 01:00").user, "bob");
+        assert_eq!(at("2025-01-27 01:00").user, "alice");
+        // before the start the rotation runs backwards
+        assert_eq!(at("2025-01-05 00:00").user, "carol");
+        let shift = at("2025-01-14 00:00");
+        assert_eq!((shift.start, shift.end), (utc("2025-01-13 01:00"), utc("2025-01-20 01:00")));
+    }
+
+    #[test]
+    fn overrides_win() {
+        let s = schedule("overrides");
+        assert_eq!(on_call(&s, utc("2025-01-20 02:00")).unwrap().user, "dave");
+        assert_eq!(on_call(&s, utc("2025-01-21 01:00")).unwrap().user, "carol");
+        let next = next_shift(&s, utc("2025-01-20 02:00")).unwrap();
+        assert_eq!((next.user.as_str(), next.start), ("carol", utc("2025-01-20 01:00")));
+    }
+
+    #[test]
+    fn runtime_override() {
+        let s = schedule("runtime");
+        let shift = add_override(&s, "erin", Duration::hours(2)).unwrap();
+        assert_eq!(on_call(&s, shift.start + Duration::hours(1)).unwrap().user, "erin");
+        assert_eq!(on_call(&s, shift.end).unwrap().user, rotation_shift(&s, shift.end).unwrap().user);
+        assert!(add_override(&s, "erin", Duration::MAX).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::bail;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::alert::Alert;
+use crate::boardcast::BROADCAST_SENDER;
+use crate::config::{Probe, CONFIG};
+use crate::exec::{self, ExecResult};
+use crate::util::{parse_cron, sleep_until_next};
+use crate::G_TOKIO_RUNTIME;
+
+/// Output included in alerts
+const OUTPUT_LEN: usize = 1000;
+
+static NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"-?\d+(\.\d+)?").unwrap());
+
+pub fn validate() -> anyhow::Result<()> {
+    for probe in &CONFIG.probe {
+        if parse_cron(&probe.schedule).is_none() {
+            bail!("[probe] invalid schedule `{}` of {}", probe.schedule, probe.name);
+        }
+        for re in [&probe.expect_regex, &probe.alert_regex, &probe.value_regex].into_iter().flatten() {
+            Regex::new(re).map_err(|e| anyhow::anyhow!("[probe] {}: {}", probe.name, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Compiled regexes of a probe
+struct Rules {
+    expect: Option<Regex>,
+    alert: Option<Regex>,
+    value: Option<Regex>,
+}
+
+impl Rules {
+    fn new(probe: &Probe) -> Self {
+        let compile = |re: &Option<String>| re.as_ref().and_then(|re| Regex::new(re).ok());
+        Rules {
+            expect: compile(&probe.expect_regex),
+            alert: compile(&probe.alert_regex),
+            value: compile(&probe.value_regex),
+        }
+    }
+
+    fn value(&self, output: &str) -> Option<f64> {
+        let text = match &self.value {
+            Some(re) => re.captures(output)?.get(1)?.as_str(),
+            None => NUMBER.find(output)?.as_str(),
+        };
+        text.trim().parse().ok()
+    }
+
+    /// Why the result is a failure, `None` when it passes
+    fn failure(&self, probe: &Probe, result: &ExecResult) -> Option<String> {
+        if result.timed_out || result.code != Some(probe.expect_exit) {
+            return Some(result.status());
+        }
+        if let Some(re) = &self.expect
+            && !re.is_match(&result.output)
+        {
+            return Some(format!("output does not match `{}`", re.as_str()));
+        }
+        if let Some(re) = &self.alert
+            && re.is_match(&result.output)
+        {
+            return Some(format!("output matches `{}`", re.as_str()));
+        }
+        if probe.above.is_none() && probe.below.is_none() {
+            return None;
+        }
+        let Some(value) = self.value(&result.output) else {
+            return Some("no value found in output".to_string());
+        };
+        if let Some(above) = probe.above
+            && value > above
+        {
+            return Some(format!("value {} above {}", value, above));
+        }
+        if let Some(below) = probe.below
+            && value < below
+        {
+            return Some(format!("value {} below {}", value, below));
+        }
+        None
+    }
+}
+
+fn excerpt(output: &str) -> String {
+    let output = output.trim();
+    match output.char_indices().nth(OUTPUT_LEN) {
+        Some((i, _)) => format!("{}…", &output[..i]),
+        None => output.to_string(),
+    }
+}
+
+async fn run_probe(probe: &'static Probe) {
+    let Some(schedule) = parse_cron(&probe.schedule) else {
+        return;
+    };
+    let rules = Rules::new(probe);
+    let title = format!("probe {} failed", probe.name);
+    let mut failing = false;
+    while sleep_until_next(&schedule).await {
+        let output = Arc::new(Mutex::new(String::new()));
+        let timeout = Duration::from_secs(probe.timeout_secs);
+        let result = match exec::run_shell(&probe.command, timeout, output).await {
+            Ok(result) => result,
+            Err(e) => {
+                error!("[probe] {} failed to execute: {}", probe.name, e);
+                continue;
+            }
+        };
+        let failure = rules.failure(probe, &result);
+        let alert = match (&failure, failing) {
+            (Some(reason), false) => {
+                warn!("[probe] {} failed: {}", probe.name, reason);
+                let body = format!("{}\n$ {}\n{}", reason, probe.command, excerpt(&result.output));
+                Some(
+                    Alert::new("probe", body)
+                        .with_title(&title)
+                        .with_severity(probe.severity)
+                        .with_label("probe", &probe.name),
+                )
+            }
+            (None, true) => {
+                info!("[probe] {} recovered", probe.name);
+                let body = format!("{} passes again\n{}", probe.name, excerpt(&result.output));
+                Some(Alert::recovery("probe", &title, body).with_label("probe", &probe.name))
+            }
+            _ => None,
+        };
+        failing = failure.is_some();
+        if let Some(alert) = alert
+            && let Err(e) = BROADCAST_SENDER.get().unwrap().send(alert).await
+        {
+            error!("[probe] failed to send alert: {}", e);
+        }
+    }
+}
+
+pub fn run_probes() {
+    for probe in &CONFIG.probe {
+        info!("[probe] {} on `{}`", probe.name, probe.schedule);
+        G_TOKIO_RUNTIME.spawn(run_probe(probe));
+    }
+}
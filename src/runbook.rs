@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::bail;
+use regex::Regex;
+use tokio::process::Command;
+
+use crate::config::{Runbook, CONFIG};
+
+pub fn validate() -> anyhow::Result<()> {
+    for (i, runbook) in CONFIG.runbook.iter().enumerate() {
+        if CONFIG.runbook[..i].iter().any(|r| r.name == runbook.name) {
+            bail!("runbook `{}` is defined twice", runbook.name);
+        }
+        for param in &runbook.param {
+            pattern(&param.pattern)
+                .map_err(|e| anyhow::anyhow!("runbook `{}` param `{}`: {}", runbook.name, param.name, e))?;
+        }
+        for arg in &runbook.args {
+            for name in placeholders(arg) {
+                if !runbook.param.iter().any(|p| p.name == name) {
+                    bail!("runbook `{}` uses undefined param `{{{}}}`", runbook.name, name);
+                }
+            }
+        }
+    }
+    for route in &CONFIG.telegram.route {
+        for action in &route.action {
+            if find(&action.runbook).is_none() {
+                bail!("route action `{}` uses unknown runbook `{}`", action.label, action.runbook);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Anchored so the pattern has to match the whole value
+fn pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+fn placeholders(arg: &str) -> Vec<&str> {
+    let mut names = vec![];
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        names.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+    names
+}
+
+pub fn find(name: &str) -> Option<&'static Runbook> {
+    CONFIG.runbook.iter().find(|r| r.name.eq_ignore_ascii_case(name))
+}
+
+pub fn timeout(runbook: &Runbook) -> Duration {
+    Duration::from_secs(runbook.timeout_secs)
+}
+
+/// `/run` usage of a runbook, e.g. `restart-gateway <instance> [env=prod]`
+pub fn usage(runbook: &Runbook) -> String {
+    let params: Vec<String> = runbook
+        .param
+        .iter()
+        .map(|p| match &p.default {
+            Some(default) => format!("[{}={}]", p.name, default),
+            None => format!("<{}>", p.name),
+        })
+        .collect();
+    format!("{} {}", runbook.name, params.join(" ")).trim().to_string()
+}
+
+/// Bind positional values to parameters, checking each against its pattern
+pub fn bind(runbook: &Runbook, values: &[&str]) -> Result<BTreeMap<String, String>, String> {
+    if values.len() > runbook.param.len() {
+        return Err(format!("too many arguments, usage: /run {}", usage(runbook)));
+    }
+    check(runbook, |i, _| values.get(i).map(|v| v.to_string()))
+}
+
+/// Bind parameters to the alert labels of the same name
+pub fn bind_labels(runbook: &Runbook, labels: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>, String> {
+    check(runbook, |_, name| labels.get(name).cloned())
+}
+
+fn check(
+    runbook: &Runbook,
+    value_of: impl Fn(usize, &str) -> Option<String>,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut bound = BTreeMap::new();
+    for (i, param) in runbook.param.iter().enumerate() {
+        let value = match (value_of(i, &param.name), &param.default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => default.clone(),
+            (None, None) => return Err(format!("missing `{}`, usage: /run {}", param.name, usage(runbook))),
+        };
+        let re = pattern(&param.pattern).map_err(|e| e.to_string())?;
+        if !re.is_match(&value) {
+            return Err(format!("`{}` does not match {} for `{}`", value, param.pattern, param.name));
+        }
+        bound.insert(param.name.clone(), value);
+    }
+    Ok(bound)
+}
+
+/// Program and arguments with parameters substituted
+pub fn argv(runbook: &Runbook, params: &BTreeMap<String, String>) -> Vec<String> {
+    let args = runbook.args.iter().map(|arg| substitute(arg, params));
+    std::iter::once(runbook.command.clone()).chain(args).collect()
+}
+
+/// Replace `{name}` in one pass, substituted values are never expanded again
+fn substitute(arg: &str, params: &BTreeMap<String, String>) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let placeholder = &rest[start..start + end + 1];
+        match params.get(&placeholder[1..placeholder.len() - 1]) {
+            Some(value) => out.push_str(value),
+            None => out.push_str(placeholder),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn command(runbook: &Runbook, params: &BTreeMap<String, String>) -> Command {
+    let argv = argv(runbook, params);
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    if let Some(cwd) = &runbook.cwd {
+        cmd.current_dir(cwd);
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runbook(text: &str) -> Runbook {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn partial_match_is_rejected() {
+        let rb = runbook(
+            r#"
+            name = "restart"
+            command = "systemctl"
+            args = ["restart", "{unit}"]
+            [[param]]
+            name = "unit"
+            pattern = "[a-z]+"
+            "#,
+        );
+        assert!(bind(&rb, &["gateway"]).is_ok());
+        assert!(bind(&rb, &["gateway; rm -rf x"]).is_err());
+        assert!(bind(&rb, &["../../etc"]).is_err());
+        assert!(bind(&rb, &[]).is_err());
+        assert!(bind(&rb, &["a", "b"]).is_err());
+    }
+
+    #[test]
+    fn alternation_is_anchored_as_a_whole() {
+        let rb = runbook(
+            r#"
+            name = "deploy"
+            command = "deploy"
+            [[param]]
+            name = "env"
+            pattern = "prod|staging"
+            "#,
+        );
+        assert!(bind(&rb, &["staging"]).is_ok());
+        assert!(bind(&rb, &["prod-x"]).is_err());
+        assert!(bind(&rb, &["x-staging"]).is_err());
+    }
+
+    #[test]
+    fn defaults_and_labels() {
+        let rb = runbook(
+            r#"
+            name = "restart"
+            command = "restart.sh"
+            [[param]]
+            name = "instance"
+            [[param]]
+            name = "env"
+            default = "prod"
+            "#,
+        );
+        let labels = BTreeMap::from([("instance".to_string(), "gw-1".to_string())]);
+        let bound = bind_labels(&rb, &labels).unwrap();
+        assert_eq!(bound["instance"], "gw-1");
+        assert_eq!(bound["env"], "prod");
+        assert!(bind_labels(&rb, &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn default_pattern_rejects_options() {
+        let rb = runbook(
+            r#"
+            name = "restart"
+            command = "restart.sh"
+            [[param]]
+            name = "instance"
+            "#,
+        );
+        assert!(bind(&rb, &["gw-1.prod"]).is_ok());
+        assert!(bind(&rb, &["--help"]).is_err());
+        assert!(bind(&rb, &["-f"]).is_err());
+        assert!(bind(&rb, &["--output=/etc/passwd"]).is_err());
+    }
+
+    #[test]
+    fn argv_substitutes_once() {
+        let rb = runbook(
+            r#"
+            name = "copy"
+            command = "cp"
+            args = ["{src}", "{dst}/{src}.bak", "{unknown}", "{open"]
+            "#,
+        );
+        let params = BTreeMap::from([
+            ("src".to_string(), "{dst}".to_string()),
+            ("dst".to_string(), "/tmp".to_string()),
+        ]);
+        assert_eq!(argv(&rb, &params), ["cp", "{dst}", "/tmp/{dst}.bak", "{unknown}", "{open"]);
+    }
+
+    #[test]
+    fn placeholder_names() {
+        assert_eq!(placeholders("{a}-{b}"), ["a", "b"]);
+        assert!(placeholders("plain").is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::process::Command;
+
+use crate::config::CONFIG;
+use crate::exec::{self, ExecResult};
+use crate::util::format_duration;
+
+const PROPERTIES: &str =
+    "Description,LoadState,ActiveState,SubState,MainPID,MemoryCurrent,NRestarts,ActiveEnterTimestampMonotonic";
+
+#[derive(Debug, Clone)]
+pub struct UnitStatus {
+    pub unit: String,
+    pub description: String,
+    pub load: String,
+    pub active: String,
+    pub sub: String,
+    pub main_pid: u32,
+    pub memory: Option<u64>,
+    pub restarts: u32,
+    pub uptime: Option<chrono::Duration>,
+}
+
+impl UnitStatus {
+    pub fn emoji(&self) -> &'static str {
+        match self.active.as_str() {
+            "active" => "🟢",
+            "activating" | "reloading" | "deactivating" => "🟡",
+            "failed" => "🔴",
+            _ => "⚪",
+        }
+    }
+
+    /// `active (running) · up 3h · 120.5 MB`
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("{} ({})", self.active, self.sub)];
+        if let Some(uptime) = self.uptime.filter(|_| self.active == "active") {
+            parts.push(format!("up {}", format_duration(uptime)));
+        }
+        if let Some(memory) = self.memory {
+            parts.push(format!("{:.1} MB", memory as f64 / 1_048_576.0));
+        }
+        if self.restarts > 0 {
+            parts.push(format!("{} restarts", self.restarts));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// The allowlisted unit matching `name`, `nginx` matches `nginx.service`
+pub fn find(name: &str) -> Option<&'static str> {
+    CONFIG
+        .systemd
+        .units
+        .iter()
+        .find(|unit| *unit == name || unit.strip_suffix(".service") == Some(name))
+        .map(|unit| unit.as_str())
+}
+
+fn timeout() -> Duration {
+    Duration::from_secs(CONFIG.systemd.timeout_secs)
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<ExecResult, String> {
+    let mut cmd = Command::new(program);
+    cmd.args(args).env("SYSTEMD_COLORS", "0");
+    let output = Arc::new(Mutex::new(String::new()));
+    exec::execute(cmd, timeout(), output)
+        .await
+        .map_err(|e| format!("failed to execute {}: {}", program, e))
+}
+
+/// Seconds since boot, the clock of `*TimestampMonotonic` properties
+fn monotonic_now() -> Option<f64> {
+    let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+    uptime.split_whitespace().next()?.parse().ok()
+}
+
+pub async fn status(unit: &str) -> Result<UnitStatus, String> {
+    let result = run("systemctl", &["show", unit, "--property", PROPERTIES]).await?;
+    if !result.success() {
+        return Err(result.output.trim().to_string());
+    }
+    let props: BTreeMap<&str, &str> = result.output.lines().filter_map(|line| line.split_once('=')).collect();
+    let prop = |name: &str| props.get(name).copied().unwrap_or_default().to_string();
+    if prop("LoadState") == "not-found" {
+        return Err(format!("unit {} not found", unit));
+    }
+    let since = prop("ActiveEnterTimestampMonotonic").parse::<u64>().unwrap_or(0);
+    let uptime = monotonic_now()
+        .filter(|_| since > 0)
+        .map(|now| chrono::Duration::seconds((now - since as f64 / 1_000_000.0).max(0.0) as i64));
+    Ok(UnitStatus {
+        unit: unit.to_string(),
+        description: prop("Description"),
+        load: prop("LoadState"),
+        active: prop("ActiveState"),
+        sub: prop("SubState"),
+        main_pid: prop("MainPID").parse().unwrap_or(0),
+        // `[not set]` or u64::MAX when memory accounting is off
+        memory: prop("MemoryCurrent").parse().ok().filter(|m| *m != u64::MAX),
+        restarts: prop("NRestarts").parse().unwrap_or(0),
+        uptime,
+    })
+}
+
+/// Last journal lines of the unit
+pub async fn journal(unit: &str, lines: usize) -> Result<String, String> {
+    let lines = lines.to_string();
+    let result = run("journalctl", &["-u", unit, "-n", &lines, "--no-pager", "-o", "short-iso"]).await?;
+    Ok(result.output.trim_end().to_string())
+}
+
+pub async fn restart(unit: &str) -> Result<ExecResult, String> {
+    run("systemctl", &["restart", unit]).await
+}
@@ -0,0 +1,287 @@
+use anyhow::{anyhow, bail};
+use log::info;
+use once_cell::sync::OnceCell;
+use teloxide::utils::{html, markdown};
+
+use crate::alert::Alert;
+use crate::config::{TemplateCfg, TemplateFormat, CONFIG};
+
+pub const SINKS: &[&str] = &["telegram", "dingtalk", "webhook", "mail"];
+const FIELDS: &[&str] = &[
+    "title", "body", "severity", "emoji", "labels", "source", "time", "host", "keyword", "text", "payload",
+];
+const ANY: &str = "*";
+
+static TEMPLATES: OnceCell<Vec<Template>> = OnceCell::new();
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Var(String),
+    /// `{{#field}} .. {{/field}}`, rendered only when field is not empty
+    Section(String, Vec<Node>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub sink: String,
+    pub source: String,
+    pub format: TemplateFormat,
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub text: String,
+    pub format: TemplateFormat,
+}
+
+impl Template {
+    pub fn compile(cfg: &TemplateCfg) -> anyhow::Result<Self> {
+        if cfg.sink != ANY && !SINKS.contains(&cfg.sink.as_str()) {
+            bail!("unknown sink `{}`, expect one of {:?} or `*`", cfg.sink, SINKS);
+        }
+        let nodes = parse(&cfg.text)
+            .map_err(|e| anyhow!("template for sink `{}` source `{}`: {}", cfg.sink, cfg.source, e))?;
+        Ok(Template {
+            sink: cfg.sink.clone(),
+            source: cfg.source.clone(),
+            format: cfg.format,
+            nodes,
+        })
+    }
+
+    fn matches(&self, sink: &str, source: &str) -> bool {
+        (self.sink == ANY || self.sink == sink) && (self.source == ANY || self.source == source)
+    }
+
+    /// sink+source > sink > source > fallback
+    fn specificity(&self) -> u8 {
+        (self.sink != ANY) as u8 * 2 + (self.source != ANY) as u8
+    }
+
+    pub fn render(&self, alert: &Alert, keyword: &str) -> Rendered {
+        let mut text = String::new();
+        render_nodes(&self.nodes, alert, keyword, self.format, &mut text);
+        Rendered {
+            text,
+            format: self.format,
+        }
+    }
+}
+
+fn parse(src: &str) -> anyhow::Result<Vec<Node>> {
+    // stack of (section name, children)
+    let mut stack: Vec<(String, Vec<Node>)> = vec![(String::new(), vec![])];
+    let mut rest = src;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            stack.last_mut().unwrap().1.push(Node::Text(rest[..start].to_string()));
+        }
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| anyhow!("unclosed `{{{{` near `{}`", &rest[start..].chars().take(20).collect::<String>()))?;
+        let tag = after[..end].trim();
+        rest = &after[end + 2..];
+
+        if let Some(name) = tag.strip_prefix('#') {
+            let name = name.trim();
+            check_field(name)?;
+            stack.push((name.to_string(), vec![]));
+        } else if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            if stack.len() == 1 || stack.last().unwrap().0 != name {
+                bail!("unexpected `{{{{/{}}}}}`", name);
+            }
+            let (name, children) = stack.pop().unwrap();
+            stack.last_mut().unwrap().1.push(Node::Section(name, children));
+        } else {
+            check_field(tag)?;
+            stack.last_mut().unwrap().1.push(Node::Var(tag.to_string()));
+        }
+    }
+    if !rest.is_empty() {
+        stack.last_mut().unwrap().1.push(Node::Text(rest.to_string()));
+    }
+    if stack.len() > 1 {
+        bail!("section `{{{{#{}}}}}` is not closed", stack.last().unwrap().0);
+    }
+    Ok(stack.pop().unwrap().1)
+}
+
+fn check_field(name: &str) -> anyhow::Result<()> {
+    if FIELDS.contains(&name) {
+        return Ok(());
+    }
+    match name.strip_prefix("labels.") {
+        Some(key) if !key.is_empty() => Ok(()),
+        _ => bail!("unknown field `{}`, expect one of {:?} or `labels.<key>`", name, FIELDS),
+    }
+}
+
+fn field(alert: &Alert, name: &str, keyword: &str) -> String {
+    match name {
+        "title" => alert.title.clone(),
+        "body" => alert.body.clone(),
+        "severity" => alert.severity.to_string(),
+        "emoji" => alert.severity.emoji().to_string(),
+        "labels" => alert.labels_str(),
+        "source" => alert.source.clone(),
+        "time" => alert.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        "host" => alert.host.clone(),
+        "keyword" => keyword.to_string(),
+        // body split by kind: plain text, or pretty printed json for code blocks
+        "text" => match alert.payload() {
+            Some(_) => String::new(),
+            None => alert.body.clone(),
+        },
+        "payload" => alert.payload().unwrap_or_default(),
+        _ => name
+            .strip_prefix("labels.")
+            .and_then(|key| alert.labels.get(key).cloned())
+            .unwrap_or_default(),
+    }
+}
+
+pub fn escape(value: &str, format: TemplateFormat) -> String {
+    match format {
+        TemplateFormat::Text => value.to_string(),
+        TemplateFormat::Markdown => markdown::escape(value),
+        TemplateFormat::Html => html::escape(value),
+    }
+}
+
+fn render_nodes(nodes: &[Node], alert: &Alert, keyword: &str, format: TemplateFormat, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(name) => out.push_str(&escape(&field(alert, name, keyword), format)),
+            Node::Section(name, children) => {
+                if !field(alert, name, keyword).is_empty() {
+                    render_nodes(children, alert, keyword, format, out);
+                }
+            }
+        }
+    }
+}
+
+fn defaults() -> Vec<TemplateCfg> {
+    let default = |sink: &str, text: &str| TemplateCfg {
+        sink: sink.to_string(),
+        source: ANY.to_string(),
+        format: TemplateFormat::Text,
+        text: text.to_string(),
+    };
+    vec![
+        TemplateCfg {
+            format: TemplateFormat::Html,
+            ..default(
+                "telegram",
+                "{{emoji}}{{#title}} <b>{{title}}</b>{{/title}}\n{{text}}{{#payload}}<pre><code class=\"language-json\">{{payload}}</code></pre>{{/payload}}",
+            )
+        },
+        default("dingtalk", "From - {{keyword}}\n{{#title}}{{title}}\n{{/title}}{{body}}"),
+        default(ANY, "{{#title}}{{title}}\n{{/title}}{{body}}"),
+    ]
+}
+
+/// Compile templates from config, fails on the first invalid one
+pub fn init_templates() -> anyhow::Result<()> {
+    let mut templates = vec![];
+    for cfg in CONFIG.template.iter().chain(defaults().iter()) {
+        templates.push(Template::compile(cfg)?);
+    }
+    info!("[template] {} templates loaded", templates.len());
+    TEMPLATES
+        .set(templates)
+        .map_err(|_| anyhow!("Failed to set templates"))
+}
+
+/// Most specific template for sink and source, user templates win over defaults
+pub fn select(sink: &str, source: &str) -> &'static Template {
+    let templates = TEMPLATES.get_or_init(|| {
+        defaults()
+            .iter()
+            .map(|cfg| Template::compile(cfg).unwrap())
+            .collect()
+    });
+    templates
+        .iter()
+        .filter(|t| t.matches(sink, source))
+        .fold(None, |best: Option<&Template>, t| match best {
+            Some(b) if b.specificity() >= t.specificity() => Some(b),
+            _ => Some(t),
+        })
+        .expect("fallback template always matches")
+}
+
+pub fn render(sink: &str, alert: &Alert, keyword: &str) -> Rendered {
+    select(sink, &alert.source).render(alert, keyword)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(text: &str, format: TemplateFormat) -> anyhow::Result<Template> {
+        Template::compile(&TemplateCfg {
+            sink: "telegram".to_string(),
+            source: ANY.to_string(),
+            format,
+            text: text.to_string(),
+        })
+    }
+
+    fn alert() -> Alert {
+        Alert::new("api", "disk <full>")
+            .with_title("Disk")
+            .with_label("mount", "/data")
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(compile("{{title}} {{body}}", TemplateFormat::Text).is_ok());
+        assert!(compile("{{nope}}", TemplateFormat::Text).is_err());
+        assert!(compile("{{labels.}}", TemplateFormat::Text).is_err());
+        assert!(compile("{{title", TemplateFormat::Text).is_err());
+        assert!(compile("{{#title}}x", TemplateFormat::Text).is_err());
+        assert!(compile("{{#title}}x{{/body}}", TemplateFormat::Text).is_err());
+        assert!(compile("x{{/title}}", TemplateFormat::Text).is_err());
+    }
+
+    #[test]
+    fn render_fields_and_sections() {
+        let tpl = compile(
+            "{{#title}}<b>{{title}}</b> {{/title}}{{body}} on {{labels.mount}}{{#labels.team}} team{{/labels.team}}",
+            TemplateFormat::Html,
+        )
+        .unwrap();
+        let rendered = tpl.render(&alert(), "");
+        assert_eq!(rendered.text, "<b>Disk</b> disk &lt;full&gt; on /data");
+        assert_eq!(rendered.format, TemplateFormat::Html);
+    }
+
+    #[test]
+    fn text_and_payload_split_the_body() {
+        let tpl = compile("[{{text}}][{{payload}}]", TemplateFormat::Text).unwrap();
+        assert_eq!(tpl.render(&alert(), "").text, "[disk <full>][]");
+        let json = Alert::new("api", r#"{"a":1}"#);
+        assert_eq!(tpl.render(&json, "").text, "[][{\n  \"a\": 1\n}]");
+    }
+
+    #[test]
+    fn specificity_order() {
+        let cfg = |sink: &str, source: &str| TemplateCfg {
+            sink: sink.to_string(),
+            source: source.to_string(),
+            format: TemplateFormat::Text,
+            text: String::new(),
+        };
+        let specificity = |sink, source| Template::compile(&cfg(sink, source)).unwrap().specificity();
+        assert!(specificity("telegram", "mail") > specificity("telegram", "*"));
+        assert!(specificity("telegram", "*") > specificity("*", "mail"));
+        assert!(specificity("*", "mail") > specificity("*", "*"));
+        assert!(Template::compile(&cfg("sms", "*")).is_err());
+    }
+}
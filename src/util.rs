@@ -0,0 +1,104 @@
+use chrono::Duration;
+
+/// Longest duration accepted from config or chat, one year
+pub const MAX_DURATION: Duration = Duration::days(365);
+
+/// Parse durations like `90s`, `30m`, `2h`, `1d`, `1w`; a bare number is minutes.
+/// `None` above [`MAX_DURATION`]
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num = num.parse::<i64>().ok()?;
+    let duration = match unit {
+        "s" => Duration::try_seconds(num),
+        "" | "m" | "min" => Duration::try_minutes(num),
+        "h" => Duration::try_hours(num),
+        "d" => Duration::try_days(num),
+        "w" => Duration::try_weeks(num),
+        _ => None,
+    }?;
+    (duration <= MAX_DURATION).then_some(duration)
+}
+
+/// `1d 2h 3m`, seconds only below one minute
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    if secs < 60 {
+        return format!("{}s", secs);
+    }
+    let (days, hours, mins) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    let mut parts = vec![];
+    if days > 0 {
+        parts.push(format!("{}d", days));
+    }
+    if hours > 0 {
+        parts.push(format!("{}h", hours));
+    }
+    if mins > 0 {
+        parts.push(format!("{}m", mins));
+    }
+    parts.join(" ")
+}
+
+/// Cron expression of 5 fields (`min hour day month weekday`), or 6-7 with seconds first
+pub fn parse_cron(expr: &str) -> Option<cron::Schedule> {
+    let expr = match expr.split_whitespace().count() {
+        5 => format!("0 {}", expr),
+        _ => expr.to_string(),
+    };
+    expr.parse().ok()
+}
+
+/// Sleep until the next local time of the schedule, `false` when there is none
+pub async fn sleep_until_next(schedule: &cron::Schedule) -> bool {
+    let Some(next) = schedule.upcoming(chrono::Local).next() else {
+        return false;
+    };
+    let wait = (next - chrono::Local::now()).to_std().unwrap_or_default();
+    tokio::time::sleep(wait).await;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90s"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration("30"), Some(Duration::minutes(30)));
+        assert_eq!(parse_duration("30min"), Some(Duration::minutes(30)));
+        assert_eq!(parse_duration(" 2h "), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("1d"), Some(Duration::days(1)));
+        assert_eq!(parse_duration("1w"), Some(Duration::weeks(1)));
+        assert_eq!(parse_duration("2y"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("-1h"), None);
+    }
+
+    #[test]
+    fn parse_duration_overflow() {
+        assert_eq!(parse_duration("99999999999w"), None);
+        assert_eq!(parse_duration("9999999999999999h"), None);
+        assert_eq!(parse_duration("200000000d"), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+        assert_eq!(parse_duration("366d"), None);
+        assert_eq!(parse_duration("365d"), Some(MAX_DURATION));
+    }
+
+    #[test]
+    fn format_duration_parts() {
+        assert_eq!(format_duration(Duration::seconds(42)), "42s");
+        assert_eq!(format_duration(Duration::seconds(-5)), "0s");
+        assert_eq!(format_duration(Duration::minutes(90)), "1h 30m");
+        assert_eq!(format_duration(Duration::days(1) + Duration::minutes(3)), "1d 3m");
+    }
+
+    #[test]
+    fn cron_with_and_without_seconds() {
+        assert!(parse_cron("*/5 * * * *").is_some());
+        assert!(parse_cron("0 0 9 * * Mon-Fri").is_some());
+        assert!(parse_cron("not a cron").is_none());
+    }
+}
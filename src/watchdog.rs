@@ -0,0 +1,227 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use anyhow::bail;
+use log::{error, info, warn};
+use regex::Regex;
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+use crate::alert::{Alert, Severity};
+use crate::boardcast::BROADCAST_SENDER;
+use crate::config::{WatchProcess, CONFIG};
+use crate::G_TOKIO_RUNTIME;
+
+pub fn validate() -> anyhow::Result<()> {
+    for watch in &CONFIG.watch.process {
+        if watch.process.is_none() && watch.cmdline.is_none() && watch.pidfile.is_none() {
+            bail!("[watch] process `{}` needs one of process, cmdline or pidfile", watch.name);
+        }
+        if let Some(cmdline) = &watch.cmdline {
+            Regex::new(cmdline).map_err(|e| anyhow::anyhow!("[watch] process `{}`: {}", watch.name, e))?;
+        }
+    }
+    Ok(())
+}
+
+fn cmdline(process: &Process) -> String {
+    process
+        .cmd()
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Processes of the watch, threads excluded
+fn find<'a>(system: &'a System, watch: &WatchProcess, cmdline_re: Option<&Regex>) -> Vec<&'a Process> {
+    if let Some(pidfile) = &watch.pidfile {
+        let pid = std::fs::read_to_string(pidfile)
+            .ok()
+            .and_then(|pid| pid.trim().parse::<u32>().ok());
+        return pid.and_then(|pid| system.process(Pid::from_u32(pid))).into_iter().collect();
+    }
+    system
+        .processes()
+        .values()
+        .filter(|p| p.thread_kind().is_none())
+        .filter(|p| {
+            watch
+                .process
+                .as_ref()
+                .is_none_or(|name| p.name().to_string_lossy().contains(name.as_str()))
+        })
+        .filter(|p| cmdline_re.is_none_or(|re| re.is_match(&cmdline(p))))
+        // do not count ourselves when botte's own command line matches
+        .filter(|p| p.pid().as_u32() != std::process::id())
+        .collect()
+}
+
+/// Conditions of one watched process currently alerting
+#[derive(Debug, Default)]
+struct State {
+    pids: BTreeSet<u32>,
+    down: bool,
+    /// restarted and not seen healthy since
+    restarted: bool,
+    count: bool,
+    rss: bool,
+    cpu: bool,
+}
+
+/// Flip `firing` and report the edge: `Some(true)` when it starts, `Some(false)` when it ends
+fn edge(firing: &mut bool, now: bool) -> Option<bool> {
+    if *firing == now {
+        return None;
+    }
+    *firing = now;
+    Some(now)
+}
+
+fn check(watch: &WatchProcess, state: &mut State, processes: &[&Process]) -> Vec<Alert> {
+    let name = &watch.name;
+    let mut alerts = vec![];
+    let mut notify = |edge: Option<bool>, title: String, body: String, severity: Severity| match edge {
+        Some(true) => alerts.push(
+            Alert::new("watch", body)
+                .with_title(title)
+                .with_severity(severity)
+                .with_label("process", name),
+        ),
+        Some(false) => alerts.push(Alert::recovery("watch", title, body).with_label("process", name)),
+        None => {}
+    };
+
+    let pids: BTreeSet<u32> = processes.iter().map(|p| p.pid().as_u32()).collect();
+    let running = !pids.is_empty();
+    let down_body = match running {
+        true => format!("{} is running again, pid {:?}", name, pids),
+        false => format!("{} is not running", name),
+    };
+    notify(edge(&mut state.down, !running), format!("{} is down", name), down_body, watch.severity);
+
+    // a restart between two samples shows up as new pids, it resolves
+    // once the new process survives the next sample
+    if running && !state.pids.is_empty() && state.pids.is_disjoint(&pids) {
+        state.restarted = true;
+        notify(
+            Some(true),
+            format!("{} restarted", name),
+            format!("{} restarted, pid {:?} -> {:?}", name, state.pids, pids),
+            Severity::Warning,
+        );
+    } else if running && state.pids == pids {
+        notify(
+            edge(&mut state.restarted, false),
+            format!("{} restarted", name),
+            format!("{} is healthy after the restart, pid {:?}", name, pids),
+            Severity::Warning,
+        );
+    }
+    if running {
+        state.pids = pids;
+    }
+
+    if running {
+        let count = processes.len();
+        notify(
+            edge(&mut state.count, count != watch.instances),
+            format!("{} instance count", name),
+            format!("{} has {} instances, expected {}", name, count, watch.instances),
+            Severity::Warning,
+        );
+    }
+
+    if let Some(limit) = watch.max_rss_mb {
+        let rss = processes.iter().map(|p| p.memory()).sum::<u64>() as f64 / 1_048_576.0;
+        notify(
+            edge(&mut state.rss, running && rss > limit),
+            format!("{} memory above {} MB", name, limit),
+            format!("{} uses {:.1} MB, limit {} MB", name, rss, limit),
+            Severity::Warning,
+        );
+    }
+
+    if let Some(limit) = watch.max_cpu {
+        let cpu: f64 = processes.iter().map(|p| p.cpu_usage() as f64).sum();
+        notify(
+            edge(&mut state.cpu, running && cpu > limit),
+            format!("{} cpu above {}%", name, limit),
+            format!("{} uses {:.1}% cpu, limit {}%", name, cpu, limit),
+            Severity::Warning,
+        );
+    }
+    alerts
+}
+
+/// Process table and the state of every watched process
+struct Watcher {
+    system: System,
+    patterns: Vec<Option<Regex>>,
+    states: Vec<State>,
+}
+
+impl Watcher {
+    fn new() -> Self {
+        let patterns = CONFIG
+            .watch
+            .process
+            .iter()
+            .map(|w| w.cmdline.as_ref().and_then(|re| Regex::new(re).ok()))
+            .collect();
+        let mut watcher = Watcher {
+            system: System::new(),
+            patterns,
+            states: CONFIG.watch.process.iter().map(|_| State::default()).collect(),
+        };
+        watcher.refresh();
+        watcher
+    }
+
+    fn refresh(&mut self) {
+        let refresh = ProcessRefreshKind::nothing()
+            .with_memory()
+            .with_cpu()
+            .with_cmd(UpdateKind::OnlyIfNotSet);
+        self.system.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh);
+    }
+
+    fn tick(&mut self) -> Vec<Alert> {
+        self.refresh();
+        let mut alerts = vec![];
+        for ((watch, state), re) in CONFIG.watch.process.iter().zip(self.states.iter_mut()).zip(&self.patterns) {
+            let processes = find(&self.system, watch, re.as_ref());
+            alerts.extend(check(watch, state, &processes));
+        }
+        alerts
+    }
+}
+
+pub fn run_watchdog() {
+    if CONFIG.watch.process.is_empty() {
+        return;
+    }
+    info!("[watch] watching {} processes", CONFIG.watch.process.len());
+    G_TOKIO_RUNTIME.spawn(async {
+        // scanning /proc and reading pidfiles blocks, keep it off the async workers
+        let mut watcher = tokio::task::spawn_blocking(Watcher::new).await.expect("watcher panicked");
+        let mut ticker = tokio::time::interval(Duration::from_secs(CONFIG.watch.interval_secs.max(1)));
+        // cpu usage needs two refreshes some time apart
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let alerts;
+            (watcher, alerts) = tokio::task::spawn_blocking(move || {
+                let alerts = watcher.tick();
+                (watcher, alerts)
+            })
+            .await
+            .expect("watcher panicked");
+            for alert in alerts {
+                warn!("[watch] {}", alert.body);
+                if let Err(e) = BROADCAST_SENDER.get().unwrap().send(alert).await {
+                    error!("[watch] failed to send alert: {}", e);
+                }
+            }
+        }
+    });
+}
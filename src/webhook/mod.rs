@@ -4,12 +4,12 @@ use crossbeam::channel::{Receiver, Sender};
 use log::info;
 use once_cell::sync::OnceCell;
 
-use crate::{config::{HookItem, HookType, CONFIG}, G_TOKIO_RUNTIME};
+use crate::{alert::Alert, config::{HookItem, HookType, CONFIG}, template, G_TOKIO_RUNTIME};
 
-pub static HOOK_TX: OnceCell<Sender<String>> = OnceCell::new();
+pub static HOOK_TX: OnceCell<Sender<Alert>> = OnceCell::new();
 
 pub fn run_webhook() {
-    if let Some(webhook) = (&CONFIG.webhook).clone() {
+    if let Some(webhook) = CONFIG.webhook.clone() {
         info!("[webhook] webhook enabled, urls: {:?}", webhook.hook_urls);
         let (tx, rx) = crossbeam::channel::bounded(64);
         HOOK_TX.set(tx).unwrap();
@@ -30,15 +30,15 @@ pub fn run_webhook() {
     }
 }
 
-fn fmt_plain(m: String) -> String {
-    m
+fn fmt_plain(alert: &Alert) -> String {
+    template::render("webhook", alert, "").text
 }
 
-fn fmt_dingtalk(kw: String, m: String) -> String {
+fn fmt_dingtalk(kw: String, alert: &Alert) -> String {
     let js_msg = serde_json::json!({
         "msgtype": "text",
         "text": serde_json::json!({
-            "content": format!("From - {}\n{}", kw, m),
+            "content": template::render("dingtalk", alert, &kw).text,
         }),
         "at": serde_json::json!({
             "isAtAll": true,
@@ -48,50 +48,46 @@ fn fmt_dingtalk(kw: String, m: String) -> String {
 }
 
 
-pub fn boardcast(urls: Vec<HookItem>, rx: Receiver<String>) {
-    while let Ok(msg) = rx.recv() {
-        info!("[webhook] received msg: {}", msg);
-        if let Ok(j) = serde_json::from_str::<serde_json::Value>(&msg) {
-            if j.get("ping").is_some() {
-                continue;
-            }
-        }
+pub fn boardcast(urls: Vec<HookItem>, rx: Receiver<Alert>) {
+    while let Ok(alert) = rx.recv() {
+        info!("[webhook] received alert: {:?}", alert);
         for u in &urls {
             info!("[webhook] send to {:?}", u);
-            let msg = msg.clone();
             let (content, u) = match u.clone() {
                 HookItem::Simple(u) => {
-                    (fmt_plain(msg), u.clone())
+                    (fmt_plain(&alert), u.clone())
                 },
                 HookItem::Detailed{ url, keyword, hook_type } => {
                     match hook_type {
-                        HookType::DingTalk => (fmt_dingtalk(keyword, msg), url.clone()),
-                        HookType::Telegram => (fmt_plain(msg), url.clone()),
+                        HookType::DingTalk => (fmt_dingtalk(keyword, &alert), url.clone()),
+                        HookType::Telegram => (fmt_plain(&alert), url.clone()),
                     }
                 }
             };
 
-            G_TOKIO_RUNTIME.spawn(async move {
-                let client = reqwest::Client::new();
-                let resp = client
-                    .post(u.clone())
-                    .header("Content-Type", "application/json")
-                    .body(content)
-                    .send()
-                    .await;
-                match resp {
-                    Ok(response) => {
-                        if response.status().is_success() {
-                            info!("[webhook] successfully sent to {}", u);
-                        } else {
-                            info!("[webhook] failed to send to {}: {}", u, response.status());
-                        }
-                    }
-                    Err(e) => {
-                        info!("[webhook] error sending to {}: {}", u, e);
-                    }
-                }
-            });
+            G_TOKIO_RUNTIME.spawn(post(u, content));
+        }
+    }
+}
+
+pub async fn post(u: String, content: String) {
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(u.clone())
+        .header("Content-Type", "application/json")
+        .body(content)
+        .send()
+        .await;
+    match resp {
+        Ok(response) => {
+            if response.status().is_success() {
+                info!("[webhook] successfully sent to {}", u);
+            } else {
+                info!("[webhook] failed to send to {}: {}", u, response.status());
+            }
+        }
+        Err(e) => {
+            info!("[webhook] error sending to {}: {}", u, e);
         }
     }
 }
\ No newline at end of file